@@ -0,0 +1,264 @@
+//! Minimal drawing backends to render documents (bills, labels) as SVG or PDF.
+//!
+//! All coordinates are in millimeters measured from the top left corner of the page, font sizes
+//! are in points. Text is set in Helvetica, which is built into every PDF reader.
+
+const PT_PER_MM: f64 = 72.0 / 25.4;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Color {
+    Black,
+    White,
+}
+
+pub(crate) trait Canvas {
+    /// draw text with its baseline starting at (x, y)
+    fn text(&mut self, x: f64, y: f64, size: f64, bold: bool, text: &str);
+    /// draw a filled rectangle with its top left corner at (x, y)
+    fn rect(&mut self, x: f64, y: f64, width: f64, height: f64, color: Color);
+    /// draw a black line
+    fn line(&mut self, x1: f64, y1: f64, x2: f64, y2: f64, width: f64);
+}
+
+/// SVG document consisting of a single page
+pub(crate) struct Svg {
+    width: f64,
+    height: f64,
+    content: String,
+}
+
+impl Svg {
+    pub(crate) fn new(width: f64, height: f64) -> Svg {
+        Svg {
+            width,
+            height,
+            content: String::new(),
+        }
+    }
+
+    pub(crate) fn finish(self) -> String {
+        format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}mm\" height=\"{h}mm\" \
+             viewBox=\"0 0 {w} {h}\">\n\
+             <rect width=\"{w}\" height=\"{h}\" fill=\"white\"/>\n{content}</svg>\n",
+            w = self.width,
+            h = self.height,
+            content = self.content
+        )
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+impl Canvas for Svg {
+    fn text(&mut self, x: f64, y: f64, size: f64, bold: bool, text: &str) {
+        self.content.push_str(&format!(
+            "<text x=\"{:.2}\" y=\"{:.2}\" font-family=\"Helvetica, Arial, sans-serif\" \
+             font-size=\"{:.3}\"{}>{}</text>\n",
+            x,
+            y,
+            size / PT_PER_MM,
+            if bold { " font-weight=\"bold\"" } else { "" },
+            escape_xml(text)
+        ));
+    }
+
+    fn rect(&mut self, x: f64, y: f64, width: f64, height: f64, color: Color) {
+        self.content.push_str(&format!(
+            "<rect x=\"{:.3}\" y=\"{:.3}\" width=\"{:.3}\" height=\"{:.3}\" fill=\"{}\"/>\n",
+            x,
+            y,
+            width,
+            height,
+            match color {
+                Color::Black => "black",
+                Color::White => "white",
+            }
+        ));
+    }
+
+    fn line(&mut self, x1: f64, y1: f64, x2: f64, y2: f64, width: f64) {
+        self.content.push_str(&format!(
+            "<line x1=\"{:.2}\" y1=\"{:.2}\" x2=\"{:.2}\" y2=\"{:.2}\" stroke=\"black\" \
+             stroke-width=\"{:.2}\"/>\n",
+            x1, y1, x2, y2, width
+        ));
+    }
+}
+
+/// PDF document with pages of equal size
+pub(crate) struct Pdf {
+    width: f64,
+    height: f64,
+    pages: Vec<String>,
+}
+
+impl Pdf {
+    /// create a document with one empty page
+    pub(crate) fn new(width: f64, height: f64) -> Pdf {
+        Pdf {
+            width,
+            height,
+            pages: vec![String::new()],
+        }
+    }
+
+    /// start a new page. Following drawing operations draw on the new page.
+    pub(crate) fn new_page(&mut self) {
+        self.pages.push(String::new());
+    }
+
+    fn content(&mut self) -> &mut String {
+        self.pages.last_mut().unwrap()
+    }
+
+    fn x(x: f64) -> f64 {
+        x * PT_PER_MM
+    }
+
+    fn y(&self, y: f64) -> f64 {
+        (self.height - y) * PT_PER_MM
+    }
+
+    pub(crate) fn finish(self) -> Vec<u8> {
+        // objects: 1 catalog, 2 page tree, 3 and 4 fonts, then page and content per page
+        let mut objects: Vec<Vec<u8>> = vec![];
+        let kids: Vec<String> = (0..self.pages.len())
+            .map(|i| format!("{} 0 R", 5 + 2 * i))
+            .collect();
+        objects.push(b"<< /Type /Catalog /Pages 2 0 R >>".to_vec());
+        objects.push(
+            format!(
+                "<< /Type /Pages /Kids [{}] /Count {} >>",
+                kids.join(" "),
+                self.pages.len()
+            )
+            .into_bytes(),
+        );
+        for font in &["Helvetica", "Helvetica-Bold"] {
+            objects.push(
+                format!(
+                    "<< /Type /Font /Subtype /Type1 /BaseFont /{} /Encoding /WinAnsiEncoding >>",
+                    font
+                )
+                .into_bytes(),
+            );
+        }
+        for (i, page) in self.pages.iter().enumerate() {
+            objects.push(
+                format!(
+                    "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {:.2} {:.2}] \
+                     /Resources << /Font << /F1 3 0 R /F2 4 0 R >> >> /Contents {} 0 R >>",
+                    Pdf::x(self.width),
+                    Pdf::x(self.height),
+                    6 + 2 * i
+                )
+                .into_bytes(),
+            );
+            let mut stream = format!("<< /Length {} >>\nstream\n", page.len()).into_bytes();
+            stream.extend_from_slice(page.as_bytes());
+            stream.extend_from_slice(b"\nendstream");
+            objects.push(stream);
+        }
+
+        let mut output: Vec<u8> = b"%PDF-1.4\n".to_vec();
+        let mut offsets: Vec<usize> = vec![];
+        for (i, object) in objects.iter().enumerate() {
+            offsets.push(output.len());
+            output.extend_from_slice(format!("{} 0 obj\n", i + 1).as_bytes());
+            output.extend_from_slice(object);
+            output.extend_from_slice(b"\nendobj\n");
+        }
+        let xref = output.len();
+        output.extend_from_slice(
+            format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes(),
+        );
+        for offset in offsets {
+            output.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+        }
+        output.extend_from_slice(
+            format!(
+                "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+                objects.len() + 1,
+                xref
+            )
+            .as_bytes(),
+        );
+        output
+    }
+}
+
+/// encode text as PDF string in WinAnsiEncoding. Characters not available are replaced by '?'.
+fn pdf_string(text: &str) -> String {
+    let mut output = String::from("(");
+    for c in text.chars() {
+        let code = match c {
+            '€' => 0x80,
+            '‘' => 0x91,
+            '’' => 0x92,
+            '“' => 0x93,
+            '”' => 0x94,
+            '•' => 0x95,
+            '–' => 0x96,
+            '—' => 0x97,
+            c if (c as u32) < 0x80 || (0xA0..=0xFF).contains(&(c as u32)) => c as u32,
+            _ => '?' as u32,
+        };
+        match code {
+            0x28 | 0x29 | 0x5C => {
+                output.push('\\');
+                output.push(code as u8 as char);
+            }
+            0x20..=0x7E => output.push(code as u8 as char),
+            _ => output.push_str(&format!("\\{:03o}", code)),
+        }
+    }
+    output.push(')');
+    output
+}
+
+impl Canvas for Pdf {
+    fn text(&mut self, x: f64, y: f64, size: f64, bold: bool, text: &str) {
+        let command = format!(
+            "0 g BT /{} {:.1} Tf {:.2} {:.2} Td {} Tj ET\n",
+            if bold { "F2" } else { "F1" },
+            size,
+            Pdf::x(x),
+            self.y(y),
+            pdf_string(text)
+        );
+        self.content().push_str(&command);
+    }
+
+    fn rect(&mut self, x: f64, y: f64, width: f64, height: f64, color: Color) {
+        let command = format!(
+            "{} g {:.3} {:.3} {:.3} {:.3} re f\n",
+            match color {
+                Color::Black => 0,
+                Color::White => 1,
+            },
+            Pdf::x(x),
+            self.y(y + height),
+            Pdf::x(width),
+            Pdf::x(height)
+        );
+        self.content().push_str(&command);
+    }
+
+    fn line(&mut self, x1: f64, y1: f64, x2: f64, y2: f64, width: f64) {
+        let command = format!(
+            "0 G {:.2} w {:.2} {:.2} m {:.2} {:.2} l S\n",
+            Pdf::x(width),
+            Pdf::x(x1),
+            self.y(y1),
+            Pdf::x(x2),
+            self.y(y2)
+        );
+        self.content().push_str(&command);
+    }
+}
@@ -0,0 +1,429 @@
+//! Detection of people entered several times, e.g. by different Abteilungen of a Kantonalverband.
+//!
+//! Pairs of people are scored by the fields they have in common. Names are compared after
+//! normalizing umlauts and accents, and phonetically with the Kölner Phonetik so that spellings
+//! like "Meier" and "Meyer" or "Schmid" and "Schmidt" match.
+//!
+//! The birthday is only known if the details of the people were loaded, see
+//! MidataConnection::load_details_of_people().
+
+use crate::midata::Person;
+
+/// Lowercase text with umlauts and accents replaced, e.g. "Müller-Lüdi" -> "mueller luedi".
+///
+/// Characters other than letters and digits separate words, whitespace is collapsed.
+pub fn normalize(text: &str) -> String {
+    let mut normalized = String::new();
+    for c in text.chars().flat_map(char::to_lowercase) {
+        match c {
+            'ä' | 'æ' => normalized.push_str("ae"),
+            'ö' | 'ø' | 'œ' => normalized.push_str("oe"),
+            'ü' => normalized.push_str("ue"),
+            'ß' => normalized.push_str("ss"),
+            'à' | 'á' | 'â' | 'ã' | 'å' => normalized.push('a'),
+            'è' | 'é' | 'ê' | 'ë' => normalized.push('e'),
+            'ì' | 'í' | 'î' | 'ï' => normalized.push('i'),
+            'ò' | 'ó' | 'ô' | 'õ' => normalized.push('o'),
+            'ù' | 'ú' | 'û' => normalized.push('u'),
+            'ç' => normalized.push('c'),
+            'ñ' => normalized.push('n'),
+            c if c.is_alphanumeric() => normalized.push(c),
+            _ => normalized.push(' '),
+        }
+    }
+    normalized
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+/// code of a word of letters a-z according to the Kölner Phonetik
+fn koelner_phonetik_word(word: &[u8]) -> String {
+    let mut codes: Vec<u8> = vec![];
+    for (i, &letter) in word.iter().enumerate() {
+        let previous = if i > 0 { Some(word[i - 1]) } else { None };
+        let next = word.get(i + 1).copied();
+        let next_in = |letters: &[u8]| next.is_some_and(|next| letters.contains(&next));
+        let previous_in = |letters: &[u8]| previous.is_some_and(|p| letters.contains(&p));
+        let code: &[u8] = match letter {
+            b'a' | b'e' | b'i' | b'j' | b'o' | b'u' | b'y' => b"0",
+            b'h' => b"",
+            b'b' => b"1",
+            b'p' if next == Some(b'h') => b"3",
+            b'p' => b"1",
+            b'd' | b't' if next_in(b"csz") => b"8",
+            b'd' | b't' => b"2",
+            b'f' | b'v' | b'w' => b"3",
+            b'g' | b'k' | b'q' => b"4",
+            b'c' if previous.is_none() && next_in(b"ahkloqrux") => b"4",
+            b'c' if previous.is_some() && !previous_in(b"sz") && next_in(b"ahkoqux") => b"4",
+            b'c' => b"8",
+            b'x' if previous_in(b"ckq") => b"8",
+            b'x' => b"48",
+            b'l' => b"5",
+            b'm' | b'n' => b"6",
+            b'r' => b"7",
+            b's' | b'z' => b"8",
+            _ => b"",
+        };
+        codes.extend_from_slice(code);
+    }
+    codes.dedup();
+    let mut output = String::new();
+    for (i, code) in codes.iter().enumerate() {
+        if i == 0 || *code != b'0' {
+            output.push(*code as char);
+        }
+    }
+    output
+}
+
+/// Phonetic code of a name according to the Kölner Phonetik, e.g. "Meier" and "Meyer" -> "67".
+///
+/// The text is normalized first and each word is coded separately, the codes are separated by
+/// spaces.
+pub fn koelner_phonetik(text: &str) -> String {
+    let normalized = normalize(text);
+    let codes: Vec<String> = normalized
+        .split(' ')
+        .map(|word| koelner_phonetik_word(word.as_bytes()))
+        .filter(|code| !code.is_empty())
+        .collect();
+    codes.join(" ")
+}
+
+/// Why two people are considered duplicates
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MatchReason {
+    /// equal first and last name after normalization
+    SameName,
+    /// first and last name sound equal
+    SimilarName,
+    /// first and last name are swapped
+    SwappedName,
+    SameNickname,
+    SameBirthday,
+    /// both birthdays are known but differ, this lowers the score
+    DifferentBirthday,
+    SameEmail,
+    /// equal street and zip code after normalization
+    SameAddress,
+}
+
+impl std::fmt::Display for MatchReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let text = match self {
+            MatchReason::SameName => "same name",
+            MatchReason::SimilarName => "similar name",
+            MatchReason::SwappedName => "swapped first and last name",
+            MatchReason::SameNickname => "same scout's name",
+            MatchReason::SameBirthday => "same birthday",
+            MatchReason::DifferentBirthday => "different birthday",
+            MatchReason::SameEmail => "same email",
+            MatchReason::SameAddress => "same address",
+        };
+        write!(f, "{}", text)
+    }
+}
+
+/// Weights of the reasons and minimal score of a duplicate
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct DuplicateOptions {
+    pub same_name: f64,
+    pub similar_name: f64,
+    pub swapped_name: f64,
+    pub same_nickname: f64,
+    pub same_birthday: f64,
+    /// usually negative
+    pub different_birthday: f64,
+    pub same_email: f64,
+    pub same_address: f64,
+    /// pairs with a lower score are not reported
+    pub min_score: f64,
+}
+
+impl DuplicateOptions {
+    /// weight of a reason
+    pub fn weight(&self, reason: MatchReason) -> f64 {
+        match reason {
+            MatchReason::SameName => self.same_name,
+            MatchReason::SimilarName => self.similar_name,
+            MatchReason::SwappedName => self.swapped_name,
+            MatchReason::SameNickname => self.same_nickname,
+            MatchReason::SameBirthday => self.same_birthday,
+            MatchReason::DifferentBirthday => self.different_birthday,
+            MatchReason::SameEmail => self.same_email,
+            MatchReason::SameAddress => self.same_address,
+        }
+    }
+}
+
+impl Default for DuplicateOptions {
+    /// Weights reporting people with the same name and birthday, email or address. Siblings
+    /// sharing the email and address of their parents are not reported.
+    fn default() -> Self {
+        DuplicateOptions {
+            same_name: 0.4,
+            similar_name: 0.3,
+            swapped_name: 0.3,
+            same_nickname: 0.2,
+            same_birthday: 0.3,
+            different_birthday: -0.5,
+            same_email: 0.2,
+            same_address: 0.2,
+            min_score: 0.6,
+        }
+    }
+}
+
+/// Pair of people which are probably the same person
+#[derive(Clone, Debug)]
+pub struct DuplicateCandidate<'a> {
+    pub first: &'a Person,
+    pub second: &'a Person,
+    /// sum of the weights of the reasons
+    pub score: f64,
+    pub reasons: Vec<MatchReason>,
+}
+
+impl std::fmt::Display for DuplicateCandidate<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let reasons: Vec<String> = self.reasons.iter().map(MatchReason::to_string).collect();
+        write!(
+            f,
+            "{} ({}) / {} ({}): {:.2} ({})",
+            self.first.display_name(),
+            self.first.id,
+            self.second.display_name(),
+            self.second.id,
+            self.score,
+            reasons.join(", ")
+        )
+    }
+}
+
+/// fields of a person prepared for comparison
+struct Features<'a> {
+    person: &'a Person,
+    first_name: String,
+    last_name: String,
+    first_name_code: String,
+    last_name_code: String,
+    nickname: String,
+    email: String,
+    address: String,
+}
+
+impl<'a> Features<'a> {
+    fn of(person: &'a Person) -> Features<'a> {
+        let text = |value: &Option<String>| normalize(value.as_deref().unwrap_or_default());
+        let address = match (text(&person.address), text(&person.zip_code)) {
+            (address, zip_code) if !address.is_empty() && !zip_code.is_empty() => {
+                format!("{} {}", address, zip_code)
+            }
+            _ => String::new(),
+        };
+        Features {
+            person,
+            first_name: text(&person.first_name),
+            last_name: text(&person.last_name),
+            first_name_code: koelner_phonetik(person.first_name.as_deref().unwrap_or_default()),
+            last_name_code: koelner_phonetik(person.last_name.as_deref().unwrap_or_default()),
+            nickname: text(&person.nickname),
+            email: person
+                .email
+                .as_deref()
+                .unwrap_or_default()
+                .trim()
+                .to_lowercase(),
+            address,
+        }
+    }
+
+    /// keys of which at least one has to be equal for two people to be compared
+    fn blocking_keys(&self) -> Vec<String> {
+        let mut keys = vec![];
+        if !self.last_name_code.is_empty() {
+            keys.push(format!("name:{}", self.last_name_code));
+        }
+        if !self.first_name_code.is_empty() {
+            // swapped first and last name
+            keys.push(format!("name:{}", self.first_name_code));
+        }
+        if let Some(birthday) = self.person.birthday {
+            keys.push(format!("birthday:{}", birthday));
+        }
+        if !self.email.is_empty() {
+            keys.push(format!("email:{}", self.email));
+        }
+        keys
+    }
+
+    fn reasons(&self, other: &Features) -> Vec<MatchReason> {
+        let equal = |a: &str, b: &str| !a.is_empty() && a == b;
+        let mut reasons = vec![];
+        if equal(&self.first_name, &other.first_name) && equal(&self.last_name, &other.last_name) {
+            reasons.push(MatchReason::SameName);
+        } else if equal(&self.first_name_code, &other.first_name_code)
+            && equal(&self.last_name_code, &other.last_name_code)
+        {
+            reasons.push(MatchReason::SimilarName);
+        } else if equal(&self.first_name_code, &other.last_name_code)
+            && equal(&self.last_name_code, &other.first_name_code)
+        {
+            reasons.push(MatchReason::SwappedName);
+        }
+        if equal(&self.nickname, &other.nickname) {
+            reasons.push(MatchReason::SameNickname);
+        }
+        match (self.person.birthday, other.person.birthday) {
+            (Some(a), Some(b)) if a == b => reasons.push(MatchReason::SameBirthday),
+            (Some(_), Some(_)) => reasons.push(MatchReason::DifferentBirthday),
+            _ => {}
+        }
+        if equal(&self.email, &other.email) {
+            reasons.push(MatchReason::SameEmail);
+        }
+        if equal(&self.address, &other.address) {
+            reasons.push(MatchReason::SameAddress);
+        }
+        reasons
+    }
+}
+
+/// Find people which are probably entered several times.
+///
+/// Only people sharing the sound of a name, the birthday or the email are compared. People with
+/// the same id (e.g. listed by several groups) are not reported.
+///
+/// # Arguments
+/// people: people to search, e.g. loaded with MidataConnection::load_people_of_groups()
+/// options: weights of the reasons and minimal score
+///
+/// # Returns
+/// pairs with a score of at least options.min_score, the most probable duplicates first
+pub fn find_duplicates<'a>(
+    people: &'a [Person],
+    options: &DuplicateOptions,
+) -> Vec<DuplicateCandidate<'a>> {
+    let features: Vec<Features> = people.iter().map(Features::of).collect();
+    let mut blocks: std::collections::HashMap<String, Vec<usize>> =
+        std::collections::HashMap::new();
+    for (i, person) in features.iter().enumerate() {
+        for key in person.blocking_keys() {
+            blocks.entry(key).or_default().push(i);
+        }
+    }
+    let mut pairs: Vec<(usize, usize)> = vec![];
+    for members in blocks.values() {
+        for (n, &i) in members.iter().enumerate() {
+            for &j in &members[n + 1..] {
+                if i != j && features[i].person.id != features[j].person.id {
+                    pairs.push((i.min(j), i.max(j)));
+                }
+            }
+        }
+    }
+    pairs.sort_unstable();
+    pairs.dedup();
+
+    let mut candidates: Vec<DuplicateCandidate> = pairs
+        .into_iter()
+        .filter_map(|(i, j)| {
+            let reasons = features[i].reasons(&features[j]);
+            let score: f64 = reasons.iter().map(|r| options.weight(*r)).sum();
+            if score < options.min_score {
+                return None;
+            }
+            Some(DuplicateCandidate {
+                first: features[i].person,
+                second: features[j].person,
+                score,
+                reasons,
+            })
+        })
+        .collect();
+    candidates.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| a.first.id.cmp(&b.first.id))
+            .then_with(|| a.second.id.cmp(&b.second.id))
+    });
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn phonetics() {
+        assert_eq!(normalize(" Müller-Lüdenscheidt "), "mueller luedenscheidt");
+        assert_eq!(normalize("François  Bégin"), "francois begin");
+        assert_eq!(koelner_phonetik("Müller-Lüdenscheidt"), "657 52682");
+        assert_eq!(koelner_phonetik("Wikipedia"), "3412");
+        assert_eq!(koelner_phonetik("Breschnew"), "17863");
+        assert_eq!(koelner_phonetik("Meier"), koelner_phonetik("Meyer"));
+        assert_eq!(koelner_phonetik("Schmid"), koelner_phonetik("Schmidt"));
+        assert_eq!(koelner_phonetik("Christoph"), "47823");
+    }
+
+    #[test]
+    fn duplicates() {
+        let people: Vec<Person> = serde_json::from_str(
+            r#"[
+                {"id": "1", "first_name": "Lukas", "last_name": "Meier", "nickname": "Fuchs",
+                 "company": false, "birthday": "2010-05-01", "email": "fam@example.com",
+                 "address": "Weg 1", "zip_code": "3000", "links": {}},
+                {"id": "2", "first_name": "Lucas", "last_name": "Meyer", "company": false,
+                 "birthday": "2010-05-01", "links": {}},
+                {"id": "3", "first_name": "Anna", "last_name": "Meier", "company": false,
+                 "birthday": "2012-07-03", "email": "fam@example.com",
+                 "address": "Weg 1", "zip_code": "3000", "links": {}},
+                {"id": "4", "first_name": "Jürg", "last_name": "Müller", "company": false,
+                 "email": "JUERG@example.com", "address": "Gasse 5", "zip_code": "8000",
+                 "links": {}},
+                {"id": "5", "first_name": "Juerg", "last_name": "Mueller", "company": false,
+                 "email": "juerg@example.com", "address": "gasse 5 ", "zip_code": "8000",
+                 "links": {}},
+                {"id": "1", "first_name": "Lukas", "last_name": "Meier", "company": false,
+                 "links": {}}
+            ]"#,
+        )
+        .unwrap();
+        let candidates = find_duplicates(&people, &DuplicateOptions::default());
+        let pairs: Vec<(&str, &str, Vec<MatchReason>)> = candidates
+            .iter()
+            .map(|c| (c.first.id.as_str(), c.second.id.as_str(), c.reasons.clone()))
+            .collect();
+        assert_eq!(
+            pairs,
+            vec![
+                (
+                    "4",
+                    "5",
+                    vec![
+                        MatchReason::SameName,
+                        MatchReason::SameEmail,
+                        MatchReason::SameAddress
+                    ]
+                ),
+                (
+                    "1",
+                    "2",
+                    vec![MatchReason::SimilarName, MatchReason::SameBirthday]
+                ),
+            ]
+        );
+        assert_eq!(
+            candidates[1].to_string(),
+            "Lukas Meier / Fuchs (1) / Lucas Meyer (2): 0.60 (similar name, same birthday)"
+        );
+
+        let strict = DuplicateOptions {
+            min_score: 0.65,
+            ..DuplicateOptions::default()
+        };
+        assert_eq!(find_duplicates(&people, &strict).len(), 1);
+    }
+}
@@ -0,0 +1,306 @@
+//! Export of people to files for spreadsheets (CSV and XLSX) and address books (vCard).
+
+use crate::midata::{Group, Person};
+
+mod vcard;
+mod xlsx;
+pub use self::vcard::*;
+pub use self::xlsx::*;
+
+/// Errors while exporting
+#[derive(Debug)]
+pub enum ExportError {
+    Io(std::io::Error),
+    Csv(csv::Error),
+    Xlsx(rust_xlsxwriter::XlsxError),
+    /// the data to export could not be loaded from midata
+    Midata(crate::midata::Error),
+    /// the layout of the labels is not usable, e.g. because it has no columns
+    InvalidLayout(String),
+}
+
+impl std::fmt::Display for ExportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ExportError::Io(e) => write!(f, "Could not write file: {}", e),
+            ExportError::Csv(e) => write!(f, "Could not write CSV: {}", e),
+            ExportError::Xlsx(e) => write!(f, "Could not write XLSX: {}", e),
+            ExportError::Midata(e) => write!(f, "Could not load from midata: {}", e),
+            ExportError::InvalidLayout(e) => write!(f, "Invalid label layout: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ExportError {}
+
+impl From<csv::Error> for ExportError {
+    fn from(e: csv::Error) -> Self {
+        ExportError::Csv(e)
+    }
+}
+
+impl From<rust_xlsxwriter::XlsxError> for ExportError {
+    fn from(e: rust_xlsxwriter::XlsxError) -> Self {
+        ExportError::Xlsx(e)
+    }
+}
+
+impl From<crate::midata::Error> for ExportError {
+    fn from(e: crate::midata::Error) -> Self {
+        ExportError::Midata(e)
+    }
+}
+
+impl From<std::io::Error> for ExportError {
+    fn from(e: std::io::Error) -> Self {
+        ExportError::Io(e)
+    }
+}
+
+/// Language of the headers
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Language {
+    #[default]
+    De,
+    Fr,
+    It,
+}
+
+/// Column of an export
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Column {
+    FirstName,
+    LastName,
+    /// scout's name
+    Nickname,
+    /// first and last name followed by the scout's name, see Person::display_name()
+    Name,
+    /// street and house number
+    Address,
+    ZipCode,
+    Town,
+    Country,
+    /// key of the household, equal for people living together
+    Household,
+    Email,
+    Birthday,
+    /// roles of the person separated by ", "
+    Roles,
+    /// path of the group the people were loaded from, see Group::path_string()
+    GroupPath,
+}
+
+impl Column {
+    /// all columns in their default order
+    pub const ALL: [Column; 13] = [
+        Column::FirstName,
+        Column::LastName,
+        Column::Nickname,
+        Column::Name,
+        Column::Address,
+        Column::ZipCode,
+        Column::Town,
+        Column::Country,
+        Column::Household,
+        Column::Email,
+        Column::Birthday,
+        Column::Roles,
+        Column::GroupPath,
+    ];
+
+    /// header of the column in the given language
+    pub fn header(&self, language: Language) -> &'static str {
+        let (de, fr, it) = match self {
+            Column::FirstName => ("Vorname", "Prénom", "Nome"),
+            Column::LastName => ("Nachname", "Nom", "Cognome"),
+            Column::Nickname => ("Pfadiname", "Totem", "Totem"),
+            Column::Name => ("Name", "Nom complet", "Nome completo"),
+            Column::Address => ("Adresse", "Adresse", "Indirizzo"),
+            Column::ZipCode => ("PLZ", "NPA", "NPA"),
+            Column::Town => ("Ort", "Lieu", "Località"),
+            Column::Country => ("Land", "Pays", "Paese"),
+            Column::Household => ("Haushalt", "Ménage", "Economia domestica"),
+            Column::Email => ("E-Mail", "E-mail", "E-mail"),
+            Column::Birthday => ("Geburtstag", "Date de naissance", "Data di nascita"),
+            Column::Roles => ("Rollen", "Rôles", "Ruoli"),
+            Column::GroupPath => ("Gruppe", "Groupe", "Gruppo"),
+        };
+        match language {
+            Language::De => de,
+            Language::Fr => fr,
+            Language::It => it,
+        }
+    }
+
+    /// value of the column for a person
+    pub fn value(&self, person: &Person, group: Option<&Group>) -> String {
+        let text = |value: &Option<String>| value.clone().unwrap_or_default();
+        match self {
+            Column::FirstName => text(&person.first_name),
+            Column::LastName => text(&person.last_name),
+            Column::Nickname => text(&person.nickname),
+            Column::Name => person.display_name(),
+            Column::Address => text(&person.address),
+            Column::ZipCode => text(&person.zip_code),
+            Column::Town => text(&person.town),
+            Column::Country => text(&person.country),
+            Column::Household => text(&person.household_key),
+            Column::Email => text(&person.email),
+            Column::Birthday => person
+                .birthday
+                .map(|birthday| birthday.format("%d.%m.%Y").to_string())
+                .unwrap_or_default(),
+            Column::Roles => {
+                let roles: Vec<String> = person
+                    .roles
+                    .iter()
+                    .map(|role| match &role.label {
+                        Some(label) if !label.is_empty() => {
+                            format!("{} ({})", role.role_type, label)
+                        }
+                        _ => role.role_type.clone(),
+                    })
+                    .collect();
+                roles.join(", ")
+            }
+            Column::GroupPath => group.map(Group::path_string).unwrap_or_default(),
+        }
+    }
+}
+
+/// Separator of the fields of a CSV file
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Delimiter {
+    /// used by Excel with Swiss locale settings
+    #[default]
+    Semicolon,
+    Comma,
+}
+
+/// Options of a CSV export
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct CsvOptions {
+    pub columns: Vec<Column>,
+    pub language: Language,
+    pub delimiter: Delimiter,
+    /// start the file with a UTF-8 byte order mark so Excel detects the encoding
+    pub bom: bool,
+}
+
+impl Default for CsvOptions {
+    /// all columns with German headers, separated by semicolons and with byte order mark, as
+    /// expected by Excel
+    fn default() -> Self {
+        CsvOptions {
+            columns: Column::ALL.to_vec(),
+            language: Language::default(),
+            delimiter: Delimiter::default(),
+            bom: true,
+        }
+    }
+}
+
+/// CSV writer using the delimiter of the options, starting with a byte order mark if set. Use
+/// the default options for files opened by Excel.
+pub fn csv_writer<W: std::io::Write>(
+    mut writer: W,
+    options: &CsvOptions,
+) -> Result<csv::Writer<W>, ExportError> {
+    if options.bom {
+        writer.write_all("\u{feff}".as_bytes())?;
+    }
+    Ok(csv::WriterBuilder::new()
+        .delimiter(match options.delimiter {
+            Delimiter::Semicolon => b';',
+            Delimiter::Comma => b',',
+        })
+        .from_writer(writer))
+}
+
+/// Write people as CSV with a header line.
+///
+/// # Arguments
+/// writer: where to write the CSV to, e.g. a file
+/// people: people to export, one per line
+/// group: group the people were loaded from, used for the column GroupPath
+/// options: columns and format of the file
+pub fn write_csv<W: std::io::Write>(
+    writer: W,
+    people: &[Person],
+    group: Option<&Group>,
+    options: &CsvOptions,
+) -> Result<(), ExportError> {
+    let mut csv = csv_writer(writer, options)?;
+    csv.write_record(
+        options
+            .columns
+            .iter()
+            .map(|column| column.header(options.language)),
+    )?;
+    for person in people {
+        csv.write_record(
+            options
+                .columns
+                .iter()
+                .map(|column| column.value(person, group)),
+        )?;
+    }
+    csv.flush()?;
+    Ok(())
+}
+
+/// People as CSV, see write_csv()
+pub fn to_csv(people: &[Person], group: Option<&Group>, options: &CsvOptions) -> Vec<u8> {
+    let mut csv: Vec<u8> = vec![];
+    write_csv(&mut csv, people, group, options).expect("writing to memory can not fail");
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn people() -> Vec<Person> {
+        serde_json::from_str(
+            r#"[
+                {"id": "10", "first_name": "Anna", "last_name": "Muster", "nickname": "Fuchs",
+                 "company": false, "address": "Weg 1", "zip_code": "3000", "town": "Bern",
+                 "birthday": "2010-05-01", "links": {},
+                 "roles": [{"id": "100", "role_type": "Leitpfadi", "created_at": "",
+                            "updated_at": ""},
+                           {"id": "101", "role_type": "Pfadi", "label": "Fähnli Adler",
+                            "created_at": "", "updated_at": ""}]},
+                {"id": "11", "first_name": "Ben", "last_name": "Muster; Meier",
+                 "company": false, "links": {}}
+            ]"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn csv_for_excel() {
+        let options = CsvOptions {
+            columns: vec![Column::Name, Column::Birthday, Column::Roles],
+            ..CsvOptions::default()
+        };
+        let csv = String::from_utf8(to_csv(&people(), None, &options)).unwrap();
+        assert_eq!(
+            csv,
+            "\u{feff}Name;Geburtstag;Rollen\n\
+             Anna Muster / Fuchs;01.05.2010;Leitpfadi, Pfadi (Fähnli Adler)\n\
+             \"Ben Muster; Meier\";;\n"
+        );
+    }
+
+    #[test]
+    fn csv_with_commas() {
+        let options = CsvOptions {
+            columns: vec![Column::FirstName, Column::ZipCode, Column::Town],
+            language: Language::Fr,
+            delimiter: Delimiter::Comma,
+            bom: false,
+        };
+        let csv = String::from_utf8(to_csv(&people(), None, &options)).unwrap();
+        assert_eq!(csv, "Prénom,NPA,Lieu\nAnna,3000,Bern\nBen,,\n");
+    }
+}
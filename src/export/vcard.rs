@@ -0,0 +1,233 @@
+use super::ExportError;
+use crate::midata::{Group, Person};
+
+/// maximal length of a line in octets, longer lines are folded
+const MAX_LINE_LENGTH: usize = 75;
+
+/// escape a text value of a vCard property
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(',', "\\,")
+        .replace(';', "\\;")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// append a content line, folded to lines of at most 75 octets
+fn push_line(vcard: &mut String, line: &str) {
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > MAX_LINE_LENGTH {
+            vcard.push_str("\r\n ");
+            length = 1;
+        }
+        vcard.push(c);
+        length += c.len_utf8();
+    }
+    vcard.push_str("\r\n");
+}
+
+/// vCard type of a phone number with the given label (e.g. "Mobil")
+fn phone_type(label: &str) -> Option<&'static str> {
+    match label.to_lowercase().as_str() {
+        "mobil" | "mobile" | "natel" | "cellulare" => Some("cell"),
+        "privat" | "privé" | "privato" => Some("home"),
+        "arbeit" | "geschäft" | "travail" | "lavoro" => Some("work"),
+        "fax" => Some("fax"),
+        _ => None,
+    }
+}
+
+/// Person as vCard 4.0.
+///
+/// # Arguments
+/// person: person to export
+/// group: group the person was loaded from. Its path is given as organization.
+pub fn to_vcard(person: &Person, group: Option<&Group>) -> String {
+    let text = |value: &Option<String>| escape(value.as_deref().unwrap_or_default());
+    let mut vcard = String::new();
+    push_line(&mut vcard, "BEGIN:VCARD");
+    push_line(&mut vcard, "VERSION:4.0");
+
+    let company_name = person.company_name.as_deref().filter(|_| person.company);
+    match company_name {
+        Some(company_name) => {
+            push_line(&mut vcard, "KIND:org");
+            push_line(&mut vcard, &format!("FN:{}", escape(company_name)));
+        }
+        None => {
+            let names: Vec<&str> = [&person.first_name, &person.last_name]
+                .iter()
+                .filter_map(|name| name.as_deref())
+                .collect();
+            let name = match names.as_slice() {
+                [] => person.nickname.clone().unwrap_or_default(),
+                names => names.join(" "),
+            };
+            push_line(&mut vcard, &format!("FN:{}", escape(&name)));
+        }
+    }
+    push_line(
+        &mut vcard,
+        &format!(
+            "N:{};{};;;",
+            text(&person.last_name),
+            text(&person.first_name)
+        ),
+    );
+    if let Some(nickname) = &person.nickname {
+        push_line(&mut vcard, &format!("NICKNAME:{}", escape(nickname)));
+    }
+    if let Some(birthday) = person.birthday {
+        push_line(&mut vcard, &format!("BDAY:{}", birthday.format("%Y%m%d")));
+    }
+    if [
+        &person.address,
+        &person.zip_code,
+        &person.town,
+        &person.country,
+    ]
+    .iter()
+    .any(|part| part.is_some())
+    {
+        push_line(
+            &mut vcard,
+            &format!(
+                "ADR;TYPE=home:;;{};{};;{};{}",
+                text(&person.address),
+                text(&person.town),
+                text(&person.zip_code),
+                text(&person.country)
+            ),
+        );
+    }
+    if let Some(email) = &person.email {
+        push_line(&mut vcard, &format!("EMAIL:{}", escape(email)));
+    }
+    for phone_number in &person.phone_numbers {
+        let phone_type = phone_number.label.as_deref().and_then(phone_type);
+        push_line(
+            &mut vcard,
+            &format!(
+                "TEL;VALUE=text{}:{}",
+                phone_type
+                    .map(|phone_type| format!(";TYPE={}", phone_type))
+                    .unwrap_or_default(),
+                escape(&phone_number.number)
+            ),
+        );
+    }
+    if let Some(picture) = &person.picture {
+        let url = if picture.starts_with('/') {
+            format!("https://db.scout.ch{}", picture)
+        } else {
+            picture.clone()
+        };
+        push_line(&mut vcard, &format!("PHOTO:{}", url));
+    }
+    if let Some(group) = group {
+        let units: Vec<String> = group.path_names().into_iter().map(escape).collect();
+        push_line(&mut vcard, &format!("ORG:{}", units.join(";")));
+    }
+    push_line(
+        &mut vcard,
+        &format!("UID:urn:x-midata:person:{}", person.id),
+    );
+    push_line(&mut vcard, "END:VCARD");
+    vcard
+}
+
+/// People as one vCard file with one vCard per person, see to_vcard()
+pub fn to_vcf(people: &[Person], group: Option<&Group>) -> String {
+    people
+        .iter()
+        .map(|person| to_vcard(person, group))
+        .collect()
+}
+
+/// Write one vCard file per group into a directory.
+///
+/// # Arguments
+/// directory: directory to write the files to, must exist
+/// groups: groups with their people
+///
+/// # Returns
+/// the paths of the files written, named after the groups (e.g. `Meute Akela.vcf`)
+pub fn write_vcf_per_group<P: AsRef<std::path::Path>>(
+    directory: P,
+    groups: &[(&Group, &[Person])],
+) -> Result<Vec<std::path::PathBuf>, ExportError> {
+    let mut paths: Vec<std::path::PathBuf> = vec![];
+    for (group, people) in groups {
+        let name: String = group
+            .name
+            .chars()
+            .map(|c| match c {
+                '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+                c => c,
+            })
+            .collect();
+        let mut path = directory.as_ref().join(format!("{}.vcf", name));
+        if paths.contains(&path) {
+            path = directory
+                .as_ref()
+                .join(format!("{} ({}).vcf", name, group.id));
+        }
+        std::fs::write(&path, to_vcf(people, Some(group)))?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vcard() {
+        let person: Person = serde_json::from_str(
+            r#"{"id": "10", "first_name": "Anna", "last_name": "Muster", "nickname": "Fuchs",
+                "company": false, "email": "anna@example.com", "birthday": "2010-05-01",
+                "address": "Weg 1", "zip_code": "3000", "town": "Bern", "country": "CH",
+                "picture": "/uploads/person/picture/10/anna.jpg", "links": {},
+                "phone_numbers": [{"id": "5", "number": "079 123 45 67", "label": "Mobil"},
+                                  {"id": "6", "number": "031 123 45 67", "label": "Mutter"}]}"#,
+        )
+        .unwrap();
+        let group: Group = serde_json::from_str(
+            r#"{"id": "2", "group_type": "Wölfe", "name": "Meute Akela; Baloo",
+                "hierarchies": [{"id": "1", "group_type": "Abteilung", "name": "Pfadi Muster"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            to_vcard(&person, Some(&group)),
+            "BEGIN:VCARD\r\n\
+             VERSION:4.0\r\n\
+             FN:Anna Muster\r\n\
+             N:Muster;Anna;;;\r\n\
+             NICKNAME:Fuchs\r\n\
+             BDAY:20100501\r\n\
+             ADR;TYPE=home:;;Weg 1;Bern;;3000;CH\r\n\
+             EMAIL:anna@example.com\r\n\
+             TEL;VALUE=text;TYPE=cell:079 123 45 67\r\n\
+             TEL;VALUE=text:031 123 45 67\r\n\
+             PHOTO:https://db.scout.ch/uploads/person/picture/10/anna.jpg\r\n\
+             ORG:Pfadi Muster;Meute Akela\\; Baloo\r\n\
+             UID:urn:x-midata:person:10\r\n\
+             END:VCARD\r\n"
+        );
+    }
+
+    #[test]
+    fn fold_long_lines() {
+        let mut vcard = String::new();
+        push_line(&mut vcard, &format!("NOTE:{}", "ä".repeat(50)));
+        let lines: Vec<&str> = vcard.split("\r\n").collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines.iter().all(|line| line.len() <= MAX_LINE_LENGTH));
+        assert_eq!(
+            vcard.replace("\r\n ", ""),
+            format!("NOTE:{}\r\n", "ä".repeat(50))
+        );
+    }
+}
@@ -0,0 +1,261 @@
+use super::{Column, ExportError, Language};
+use crate::midata::{Error, Group, MemberCounts, MidataConnection, Person};
+use rust_xlsxwriter::{Color, Format, FormatBorder, Workbook, Worksheet};
+
+/// maximal length of the name of a worksheet
+const MAX_SHEET_NAME_LENGTH: usize = 31;
+
+/// Options of an XLSX export
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct XlsxOptions {
+    pub columns: Vec<Column>,
+    pub language: Language,
+}
+
+impl Default for XlsxOptions {
+    /// all columns with German headers
+    fn default() -> Self {
+        XlsxOptions {
+            columns: Column::ALL.to_vec(),
+            language: Language::default(),
+        }
+    }
+}
+
+/// name of the summary sheet and headers of its columns
+fn summary_headers(language: Language) -> [&'static str; 5] {
+    match language {
+        Language::De => ["Übersicht", "Gruppe", "TN", "Leiter", "Total"],
+        Language::Fr => ["Résumé", "Groupe", "Participants", "Responsables", "Total"],
+        Language::It => ["Riepilogo", "Gruppo", "Partecipanti", "Capi", "Totale"],
+    }
+}
+
+/// Name of a worksheet: Excel does not allow some characters, more than 31 characters and
+/// several sheets with the same name.
+fn sheet_name(name: &str, used: &[String]) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| match c {
+            '[' | ']' | ':' | '*' | '?' | '/' | '\\' => '_',
+            c => c,
+        })
+        .collect();
+    let name = name.trim_matches('\'').trim();
+    let name = if name.is_empty() { "_" } else { name };
+    let mut candidate: String = name.chars().take(MAX_SHEET_NAME_LENGTH).collect();
+    let mut number = 2;
+    while used
+        .iter()
+        .any(|used| used.to_lowercase() == candidate.to_lowercase())
+    {
+        let suffix = format!(" ({})", number);
+        let prefix: String = name
+            .chars()
+            .take(MAX_SHEET_NAME_LENGTH - suffix.chars().count())
+            .collect();
+        candidate = format!("{}{}", prefix, suffix);
+        number += 1;
+    }
+    candidate
+}
+
+fn header_format() -> Format {
+    Format::new()
+        .set_bold()
+        .set_background_color(Color::RGB(0xD9E1F2))
+        .set_border_bottom(FormatBorder::Thin)
+}
+
+/// write the header row, freeze it and add an auto-filter to the given number of rows
+fn write_header(
+    worksheet: &mut Worksheet,
+    headers: &[&str],
+    rows: usize,
+) -> Result<(), ExportError> {
+    let format = header_format();
+    for (column, header) in headers.iter().enumerate() {
+        worksheet.write_string_with_format(0, column as u16, *header, &format)?;
+    }
+    worksheet.set_freeze_panes(1, 0)?;
+    if !headers.is_empty() {
+        worksheet.autofilter(0, 0, rows as u32, headers.len() as u16 - 1)?;
+    }
+    Ok(())
+}
+
+/// Workbook with a summary sheet and one sheet per group listing its people.
+///
+/// # Arguments
+/// groups: groups with their people, one sheet per group in the given order
+/// options: columns of the group sheets and language of the headers
+///
+/// # Note
+/// The summary sheet counts the participants and the leaders of each group, see MemberCounts.
+/// People without roles are only counted in the total.
+pub fn to_xlsx(
+    groups: &[(&Group, &[Person])],
+    options: &XlsxOptions,
+) -> Result<Vec<u8>, ExportError> {
+    let mut workbook = Workbook::new();
+    let mut used_names: Vec<String> = vec![];
+
+    let headers = summary_headers(options.language);
+    let summary = workbook.add_worksheet();
+    summary.set_name(headers[0])?;
+    used_names.push(headers[0].to_string());
+    write_header(summary, &headers[1..], groups.len())?;
+    for (row, (group, people)) in groups.iter().enumerate() {
+        let row = row as u32 + 1;
+        let counts = MemberCounts::of(people);
+        summary.write_string(row, 0, &group.name)?;
+        summary.write_number(row, 1, counts.tn as f64)?;
+        summary.write_number(row, 2, counts.leiter as f64)?;
+        summary.write_number(row, 3, counts.total as f64)?;
+    }
+    summary.autofit();
+
+    let columns: Vec<&str> = options
+        .columns
+        .iter()
+        .map(|column| column.header(options.language))
+        .collect();
+    for (group, people) in groups {
+        let name = sheet_name(&group.name, &used_names);
+        used_names.push(name.clone());
+        let worksheet = workbook.add_worksheet();
+        worksheet.set_name(&name)?;
+        write_header(worksheet, &columns, people.len())?;
+        for (row, person) in people.iter().enumerate() {
+            for (column, value) in options.columns.iter().enumerate() {
+                let value = value.value(person, Some(group));
+                if !value.is_empty() {
+                    worksheet.write_string(row as u32 + 1, column as u16, &value)?;
+                }
+            }
+        }
+        worksheet.autofit();
+    }
+
+    Ok(workbook.save_to_buffer()?)
+}
+
+/// Workbook with one sheet per child group of a loaded group, see to_xlsx().
+///
+/// # Arguments
+/// group: group loaded with load_group(), so that its children are known
+/// connection: connection to load the people of the children with
+/// options: columns of the group sheets and language of the headers
+///
+/// # Errors
+/// ExportError::Midata if the people of a child could not be loaded.
+pub fn group_tree_to_xlsx(
+    group: &Group,
+    connection: &MidataConnection,
+    options: &XlsxOptions,
+) -> Result<Vec<u8>, ExportError> {
+    let people: Vec<Vec<Person>> = group
+        .chilrden
+        .iter()
+        .map(|child| {
+            let id = child.id.parse().map_err(|_| Error::UnexpectedResponse)?;
+            connection.try_load_people_of_groups(vec![id])
+        })
+        .collect::<Result<_, Error>>()?;
+    let sheets: Vec<(&Group, &[Person])> = group
+        .chilrden
+        .iter()
+        .zip(people.iter().map(Vec::as_slice))
+        .collect();
+    to_xlsx(&sheets, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sheet_names() {
+        let used = vec!["Übersicht".to_string(), "Meute".to_string()];
+        assert_eq!(sheet_name("Trupp 1/2", &used), "Trupp 1_2");
+        assert_eq!(sheet_name("meute", &used), "meute (2)");
+        assert_eq!(
+            sheet_name("Pfadistufe der Abteilung Muster Bern", &[]),
+            "Pfadistufe der Abteilung Muster"
+        );
+    }
+
+    #[test]
+    fn workbook() {
+        let groups: Vec<Group> = serde_json::from_str(
+            r#"[
+                {"id": "2", "group_type": "Wölfe", "name": "Meute Akela"},
+                {"id": "3", "group_type": "Pfadi", "name": "Trupp Falke"}
+            ]"#,
+        )
+        .unwrap();
+        let people: Vec<Person> = serde_json::from_str(
+            r#"[
+                {"id": "10", "first_name": "Anna", "company": false, "links": {},
+                 "roles": [{"id": "100", "role_type": "Wolf", "created_at": "",
+                            "updated_at": ""}]}
+            ]"#,
+        )
+        .unwrap();
+        let xlsx = to_xlsx(
+            &[(&groups[0], &people), (&groups[1], &[])],
+            &XlsxOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(&xlsx[..2], b"PK");
+
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(xlsx)).unwrap();
+        let mut read = |name: &str| {
+            let mut content = String::new();
+            std::io::Read::read_to_string(&mut archive.by_name(name).unwrap(), &mut content)
+                .unwrap();
+            content
+        };
+        let workbook = read("xl/workbook.xml");
+        let sheets: Vec<&str> = workbook
+            .split("<sheet name=\"")
+            .skip(1)
+            .filter_map(|sheet| sheet.split('"').next())
+            .collect();
+        assert_eq!(sheets, vec!["Übersicht", "Meute Akela", "Trupp Falke"]);
+
+        let summary = read("xl/worksheets/sheet1.xml");
+        // numeric cells as (cell, value): TN, leaders and total of both groups
+        let numbers: Vec<(&str, &str)> = summary
+            .split("<c r=\"")
+            .skip(1)
+            .filter(|cell| {
+                !cell
+                    .split('>')
+                    .next()
+                    .unwrap_or_default()
+                    .contains("t=\"s\"")
+            })
+            .filter_map(|cell| {
+                let value = cell.split("<v>").nth(1)?.split("</v>").next()?;
+                Some((cell.split('"').next()?, value))
+            })
+            .collect();
+        assert_eq!(
+            numbers,
+            vec![
+                ("B2", "1"),
+                ("C2", "0"),
+                ("D2", "1"),
+                ("B3", "0"),
+                ("C3", "0"),
+                ("D3", "0")
+            ]
+        );
+        assert!(summary.contains(r#"<autoFilter ref="A1:D3"/>"#));
+        assert!(summary.contains(r#"ySplit="1""#));
+        let group_sheet = read("xl/worksheets/sheet2.xml");
+        assert!(group_sheet.contains("<autoFilter ref=\"A1:"));
+        assert!(group_sheet.contains(r#"state="frozen""#));
+    }
+}
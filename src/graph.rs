@@ -0,0 +1,345 @@
+//! Group trees as diagrams, e.g. to show the structure of an Abteilung at the
+//! Abteilungsversammlung.
+//!
+//! A tree is rendered as Graphviz DOT (`dot -Tpdf groups.dot -o groups.pdf`) or as Mermaid
+//! flowchart, which is displayed by many markdown viewers.
+
+use crate::midata::{Group, GroupType, MemberCounts, MidataConnection};
+
+/// Group with the groups below it
+#[derive(Clone, Debug)]
+pub struct GroupTree {
+    pub group: Group,
+    /// not loaded unless load_member_counts() was called
+    pub members: Option<MemberCounts>,
+    pub children: Vec<GroupTree>,
+}
+
+impl GroupTree {
+    /// Tree of a group and its children (Group::chilrden), recursively.
+    ///
+    /// A group loaded with MidataConnection::load_group() only knows its direct children, use
+    /// load() for the whole tree.
+    pub fn of(group: &Group) -> GroupTree {
+        GroupTree {
+            group: group.clone(),
+            members: None,
+            children: group.chilrden.iter().map(GroupTree::of).collect(),
+        }
+    }
+
+    /// Load a group and the groups below it.
+    ///
+    /// # Arguments
+    /// connection: connection to load the groups with
+    /// id: id of the root group, e.g. of an Abteilung
+    /// max_depth: levels to load below the root, all if None
+    pub fn load(connection: &MidataConnection, id: u16, max_depth: Option<usize>) -> GroupTree {
+        let mut tree = GroupTree::of(&connection.load_group(id));
+        let mut depth = 1;
+        let mut level: Vec<&mut GroupTree> = vec![&mut tree];
+        while max_depth.is_none_or(|max_depth| depth < max_depth) {
+            let mut children: Vec<&mut GroupTree> = level
+                .into_iter()
+                .flat_map(|node| node.children.iter_mut())
+                .collect();
+            if children.is_empty() {
+                break;
+            }
+            let ids: Vec<u16> = children
+                .iter()
+                .filter_map(|child| child.group.id.parse().ok())
+                .collect();
+            let groups = connection.load_groups(ids);
+            for child in children.iter_mut() {
+                if let Some(group) = groups.iter().find(|group| group.id == child.group.id) {
+                    **child = GroupTree::of(group);
+                }
+            }
+            level = children;
+            depth += 1;
+        }
+        if let Some(max_depth) = max_depth {
+            tree.prune(max_depth);
+        }
+        tree
+    }
+
+    /// remove the groups more than max_depth levels below this group
+    fn prune(&mut self, max_depth: usize) {
+        if max_depth == 0 {
+            self.children.clear();
+        }
+        for child in &mut self.children {
+            child.prune(max_depth.saturating_sub(1));
+        }
+    }
+
+    /// Load the people of all groups in the tree and count them.
+    pub fn load_member_counts(&mut self, connection: &MidataConnection) {
+        if let Ok(id) = self.group.id.parse() {
+            self.members = Some(MemberCounts::of(&connection.load_people_of_group(id)));
+        }
+        for child in &mut self.children {
+            child.load_member_counts(connection);
+        }
+    }
+
+    /// groups to draw, each with the id of the nearest drawn ancestor
+    fn visible<'a>(
+        &'a self,
+        options: &GraphOptions,
+        depth: usize,
+        parent: Option<&'a str>,
+        nodes: &mut Vec<(&'a GroupTree, Option<&'a str>)>,
+    ) {
+        let shown = parent.is_none()
+            || options
+                .group_types
+                .as_ref()
+                .is_none_or(|types| types.contains(&self.group.group_type()));
+        let parent = if shown {
+            nodes.push((self, parent));
+            Some(self.group.id.as_str())
+        } else {
+            parent
+        };
+        if options.max_depth.is_none_or(|max_depth| depth < max_depth) {
+            for child in &self.children {
+                child.visible(options, depth + 1, parent, nodes);
+            }
+        }
+    }
+
+    fn nodes(&self, options: &GraphOptions) -> Vec<(&GroupTree, Option<&str>)> {
+        let mut nodes = vec![];
+        self.visible(options, 0, None, &mut nodes);
+        nodes
+    }
+
+    /// lines of the label of the group
+    fn label(&self, options: &GraphOptions) -> Vec<String> {
+        let mut lines = vec![
+            self.group.name.clone(),
+            type_label(&self.group.group_type()).to_string(),
+        ];
+        if options.member_counts {
+            if let Some(members) = &self.members {
+                lines.push(format!("{} TN / {} Leiter", members.tn, members.leiter));
+            }
+        }
+        lines
+    }
+
+    /// Render the tree as Graphviz DOT.
+    pub fn to_dot(&self, options: &GraphOptions) -> String {
+        let escape = |text: &str| text.replace('\\', "\\\\").replace('"', "\\\"");
+        let mut dot = String::from("digraph groups {\n");
+        dot.push_str("    rankdir=TB;\n");
+        dot.push_str(&format!(
+            "    node [shape=box, style=\"{}\", fontname=\"Helvetica\"];\n",
+            if options.colors {
+                "rounded,filled"
+            } else {
+                "rounded"
+            }
+        ));
+        let nodes = self.nodes(options);
+        for (node, _) in &nodes {
+            let label: Vec<String> = node.label(options).iter().map(|l| escape(l)).collect();
+            let color = if options.colors {
+                format!(", fillcolor=\"{}\"", color(&node.group.group_type()))
+            } else {
+                String::new()
+            };
+            dot.push_str(&format!(
+                "    g{} [label=\"{}\"{}];\n",
+                node.group.id,
+                label.join("\\n"),
+                color
+            ));
+        }
+        for (node, parent) in &nodes {
+            if let Some(parent) = parent {
+                dot.push_str(&format!("    g{} -> g{};\n", parent, node.group.id));
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Render the tree as Mermaid flowchart.
+    pub fn to_mermaid(&self, options: &GraphOptions) -> String {
+        let escape = |text: &str| text.replace('"', "#quot;");
+        let mut mermaid = String::from("flowchart TD\n");
+        let nodes = self.nodes(options);
+        for (node, _) in &nodes {
+            let label: Vec<String> = node.label(options).iter().map(|l| escape(l)).collect();
+            mermaid.push_str(&format!(
+                "    g{}[\"{}\"]\n",
+                node.group.id,
+                label.join("<br/>")
+            ));
+        }
+        for (node, parent) in &nodes {
+            if let Some(parent) = parent {
+                mermaid.push_str(&format!("    g{} --> g{}\n", parent, node.group.id));
+            }
+        }
+        if options.colors {
+            let mut classes: Vec<(String, &str, Vec<&str>)> = vec![];
+            for (node, _) in &nodes {
+                let group_type = node.group.group_type();
+                let class = class_name(&group_type);
+                match classes.iter_mut().find(|(c, _, _)| *c == class) {
+                    Some((_, _, ids)) => ids.push(&node.group.id),
+                    None => classes.push((class, color(&group_type), vec![&node.group.id])),
+                }
+            }
+            for (class, color, ids) in classes {
+                let ids: Vec<String> = ids.iter().map(|id| format!("g{}", id)).collect();
+                mermaid.push_str(&format!("    classDef {} fill:{}\n", class, color));
+                mermaid.push_str(&format!("    class {} {}\n", ids.join(","), class));
+            }
+        }
+        mermaid
+    }
+}
+
+/// Which groups to draw and how
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct GraphOptions {
+    /// levels to draw below the root, all if None
+    pub max_depth: Option<usize>,
+    /// types of the groups to draw, all if None. The root is always drawn, groups below hidden
+    /// groups are connected to the nearest drawn ancestor.
+    pub group_types: Option<Vec<GroupType>>,
+    /// add the number of participants and leaders to the labels, if loaded
+    pub member_counts: bool,
+    /// fill the groups with the color of their type
+    pub colors: bool,
+}
+
+impl Default for GraphOptions {
+    /// all groups, colored and with member counts
+    fn default() -> Self {
+        GraphOptions {
+            max_depth: None,
+            group_types: None,
+            member_counts: true,
+            colors: true,
+        }
+    }
+}
+
+/// German name of a group type as shown in the diagrams
+fn type_label(group_type: &GroupType) -> &str {
+    match group_type {
+        GroupType::Bund => "Bund",
+        GroupType::Kantonalverband => "Kantonalverband",
+        GroupType::Region => "Region",
+        GroupType::Abteilung => "Abteilung",
+        GroupType::Biber => "Biberstufe",
+        GroupType::Woelfe => "Wolfsstufe",
+        GroupType::Pfadi => "Pfadistufe",
+        GroupType::Pio => "Piostufe",
+        GroupType::Rover => "Roverstufe",
+        GroupType::Pta => "PTA",
+        GroupType::Other(group_type) => group_type.rsplit("::").next().unwrap_or(group_type),
+    }
+}
+
+/// fill color of the groups of a type
+fn color(group_type: &GroupType) -> &'static str {
+    match group_type {
+        GroupType::Bund | GroupType::Kantonalverband | GroupType::Region => "#e0e0e0",
+        GroupType::Abteilung => "#ffffff",
+        GroupType::Biber => "#f9d5e5",
+        GroupType::Woelfe => "#fff2b3",
+        GroupType::Pfadi => "#c6dcf5",
+        GroupType::Pio => "#f8c8c0",
+        GroupType::Rover => "#cdeac7",
+        GroupType::Pta => "#e2d3f0",
+        GroupType::Other(_) => "#f5f5f5",
+    }
+}
+
+/// name of the Mermaid class of a group type
+fn class_name(group_type: &GroupType) -> String {
+    let name: String = type_label(group_type)
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .collect();
+    match name.is_empty() {
+        true => "other".to_string(),
+        false => name.to_lowercase(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree() -> GroupTree {
+        let groups: Vec<Group> = serde_json::from_str(
+            r#"[
+                {"id": "1", "group_type": "Group::Abteilung", "name": "Pfadi \"Muster\""},
+                {"id": "2", "group_type": "Group::Woelfe", "name": "Wolfsstufe"},
+                {"id": "3", "group_type": "Group::Woelfe", "name": "Meute Akela"},
+                {"id": "4", "group_type": "Group::Pfadi", "name": "Trupp Falke"}
+            ]"#,
+        )
+        .unwrap();
+        let node = |i: usize, children: Vec<GroupTree>| GroupTree {
+            group: groups[i].clone(),
+            members: Some(MemberCounts {
+                tn: i * 2,
+                leiter: i,
+                total: i * 3,
+            }),
+            children,
+        };
+        node(0, vec![node(1, vec![node(2, vec![])]), node(3, vec![])])
+    }
+
+    #[test]
+    fn dot() {
+        let options = GraphOptions {
+            max_depth: Some(1),
+            member_counts: false,
+            ..GraphOptions::default()
+        };
+        assert_eq!(
+            tree().to_dot(&options),
+            "digraph groups {\n    \
+                 rankdir=TB;\n    \
+                 node [shape=box, style=\"rounded,filled\", fontname=\"Helvetica\"];\n    \
+                 g1 [label=\"Pfadi \\\"Muster\\\"\\nAbteilung\", fillcolor=\"#ffffff\"];\n    \
+                 g2 [label=\"Wolfsstufe\\nWolfsstufe\", fillcolor=\"#fff2b3\"];\n    \
+                 g4 [label=\"Trupp Falke\\nPfadistufe\", fillcolor=\"#c6dcf5\"];\n    \
+                 g1 -> g2;\n    \
+                 g1 -> g4;\n\
+             }\n"
+        );
+    }
+
+    #[test]
+    fn mermaid() {
+        let options = GraphOptions {
+            group_types: Some(vec![GroupType::Woelfe]),
+            colors: false,
+            ..GraphOptions::default()
+        };
+        let mut tree = tree();
+        tree.children[0].group =
+            serde_json::from_str(r#"{"id": "2", "group_type": "Group::Region", "name": "Stufe"}"#)
+                .unwrap();
+        assert_eq!(
+            tree.to_mermaid(&options),
+            "flowchart TD\n    \
+                 g1[\"Pfadi #quot;Muster#quot;<br/>Abteilung<br/>0 TN / 0 Leiter\"]\n    \
+                 g3[\"Meute Akela<br/>Wolfsstufe<br/>4 TN / 2 Leiter\"]\n    \
+                 g1 --> g3\n"
+        );
+    }
+}
@@ -0,0 +1,469 @@
+//! Households of people living together, to send one letter per family instead of one per kid.
+//!
+//! People are grouped by their household key in midata. People without household key join the
+//! household of someone living at the same address. Each household gets a salutation (e.g.
+//! "Familie Muster") and a canonical postal address, which can be printed on address labels (PDF)
+//! or exported as CSV for a mail merge.
+
+use crate::drawing::{Canvas, Pdf};
+use crate::export::{csv_writer, CsvOptions, ExportError, Language};
+use crate::midata::Person;
+use crate::qrbill::Address;
+use std::collections::{HashMap, HashSet};
+
+/// People living together
+#[derive(Debug, Clone)]
+pub struct Household<'a> {
+    /// household key in midata. None if the members were grouped by their address.
+    pub household_key: Option<String>,
+    pub members: Vec<&'a Person>,
+}
+
+/// normalized address to compare addresses of people without household key
+fn address_key(address: &Address) -> String {
+    let parts = [
+        address.street.as_deref().unwrap_or_default(),
+        address.house_number.as_deref().unwrap_or_default(),
+        &address.zip_code,
+        &address.town,
+        &address.country,
+    ];
+    let words: Vec<String> = parts
+        .iter()
+        .flat_map(|part| part.split_whitespace())
+        .map(str::to_lowercase)
+        .collect();
+    words.join(" ")
+}
+
+impl<'a> Household<'a> {
+    /// Salutation of the household.
+    ///
+    /// A single person is addressed by name, several people by their last names, e.g. "Familie
+    /// Muster" or "Familie Muster und Meier".
+    pub fn salutation(&self, language: Language) -> String {
+        if let [person] = self.members.as_slice() {
+            return match Address::of_person(person) {
+                Some(address) => address.name,
+                None => person.display_name(),
+            };
+        }
+        let mut last_names: Vec<&str> = vec![];
+        for person in &self.members {
+            if let Some(last_name) = person.last_name.as_deref().map(str::trim) {
+                if !last_name.is_empty() && !last_names.contains(&last_name) {
+                    last_names.push(last_name);
+                }
+            }
+        }
+        let (family, and) = match language {
+            Language::De => ("Familie", "und"),
+            Language::Fr => ("Famille", "et"),
+            Language::It => ("Famiglia", "e"),
+        };
+        match last_names.split_last() {
+            None => family.to_string(),
+            Some((last, [])) => format!("{} {}", family, last),
+            Some((last, others)) => format!("{} {} {} {}", family, others.join(", "), and, last),
+        }
+    }
+
+    /// Postal address of the household with the salutation as name.
+    ///
+    /// The address most members have is used, on a tie the one of the first member. None if no
+    /// member has a complete address.
+    pub fn address(&self, language: Language) -> Option<Address> {
+        let addresses: Vec<Address> = self
+            .members
+            .iter()
+            .filter_map(|person| Address::of_person(person))
+            .collect();
+        let keys: Vec<String> = addresses.iter().map(address_key).collect();
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for key in &keys {
+            *counts.entry(key).or_default() += 1;
+        }
+        let mut best: Option<usize> = None;
+        for (i, key) in keys.iter().enumerate() {
+            if best.is_none_or(|best| counts[key.as_str()] > counts[keys[best].as_str()]) {
+                best = Some(i);
+            }
+        }
+        let mut address = addresses.into_iter().nth(best?)?;
+        address.name = self.salutation(language);
+        Some(address)
+    }
+}
+
+/// Group people into households.
+///
+/// People with the same household key form a household. People without household key join the
+/// household of someone with the same address, or form a household with the people without key
+/// living there. People listed several times (e.g. when loaded from several groups) are only
+/// added once. The households and their members are in the order of the list.
+pub fn households(people: &[Person]) -> Vec<Household<'_>> {
+    let mut ids: HashSet<&str> = HashSet::new();
+    let unique: Vec<&Person> = people
+        .iter()
+        .filter(|person| ids.insert(person.id.as_str()))
+        .collect();
+    let household_key =
+        |person: &Person| person.household_key.clone().filter(|key| !key.is_empty());
+    let address = |person: &Person| Address::of_person(person).map(|a| address_key(&a));
+
+    // positions of the members in unique, first people with key then the others
+    let mut members: Vec<(Option<String>, Vec<usize>)> = vec![];
+    // index in members by household key and by address
+    let mut by_key: HashMap<String, usize> = HashMap::new();
+    let mut by_address: HashMap<String, usize> = HashMap::new();
+    for (position, person) in unique.iter().enumerate() {
+        if let Some(key) = household_key(person) {
+            let index = *by_key.entry(key.clone()).or_insert_with(|| {
+                members.push((Some(key), vec![]));
+                members.len() - 1
+            });
+            members[index].1.push(position);
+            if let Some(address) = address(person) {
+                by_address.entry(address).or_insert(index);
+            }
+        }
+    }
+    for (position, person) in unique.iter().enumerate() {
+        if household_key(person).is_some() {
+            continue;
+        }
+        let address = address(person);
+        let existing = address
+            .as_ref()
+            .and_then(|address| by_address.get(address))
+            .copied();
+        match existing {
+            Some(index) => members[index].1.push(position),
+            None => {
+                members.push((None, vec![position]));
+                if let Some(address) = address {
+                    by_address.insert(address, members.len() - 1);
+                }
+            }
+        }
+    }
+
+    for (_, positions) in &mut members {
+        positions.sort_unstable();
+    }
+    members.sort_by_key(|(_, positions)| positions[0]);
+    members
+        .into_iter()
+        .map(|(household_key, positions)| Household {
+            household_key,
+            members: positions.into_iter().map(|i| unique[i]).collect(),
+        })
+        .collect()
+}
+
+/// labels may exceed the page by this many millimeters due to rounding of the label sizes
+const LAYOUT_TOLERANCE: f64 = 0.5;
+
+/// Sheet of address labels, all sizes in millimeters
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct LabelLayout {
+    pub page_width: f64,
+    pub page_height: f64,
+    pub columns: usize,
+    pub rows: usize,
+    pub label_width: f64,
+    pub label_height: f64,
+    /// distance of the first column from the left edge of the page
+    pub margin_left: f64,
+    /// distance of the first row from the top edge of the page
+    pub margin_top: f64,
+    /// horizontal space between two labels
+    pub gap_x: f64,
+    /// vertical space between two labels
+    pub gap_y: f64,
+    /// font size of the address in points
+    pub font_size: f64,
+}
+
+impl LabelLayout {
+    /// A4 sheet with 3 x 8 labels of 70 x 35 mm, e.g. Avery Zweckform 3422
+    pub const AVERY_3X8: LabelLayout = LabelLayout {
+        page_width: 210.0,
+        page_height: 297.0,
+        columns: 3,
+        rows: 8,
+        label_width: 70.0,
+        label_height: 35.0,
+        margin_left: 0.0,
+        margin_top: 8.5,
+        gap_x: 0.0,
+        gap_y: 0.0,
+        font_size: 10.0,
+    };
+
+    /// A4 sheet with 3 x 7 labels of 70 x 42.3 mm, e.g. Avery Zweckform 3652
+    pub const AVERY_3X7: LabelLayout = LabelLayout {
+        page_width: 210.0,
+        page_height: 297.0,
+        columns: 3,
+        rows: 7,
+        label_width: 70.0,
+        label_height: 42.3,
+        margin_left: 0.0,
+        margin_top: 0.45,
+        gap_x: 0.0,
+        gap_y: 0.0,
+        font_size: 10.0,
+    };
+
+    fn labels_per_page(&self) -> usize {
+        self.columns * self.rows
+    }
+
+    /// Check that the layout has labels and that they fit on the page.
+    pub fn validate(&self) -> Result<(), ExportError> {
+        let invalid = |reason: &str| Err(ExportError::InvalidLayout(reason.to_string()));
+        if self.columns == 0 || self.rows == 0 {
+            return invalid("no columns or rows");
+        }
+        if self.label_width <= 0.0 || self.label_height <= 0.0 || self.font_size <= 0.0 {
+            return invalid("labels or font without size");
+        }
+        let width = self.margin_left
+            + self.columns as f64 * self.label_width
+            + (self.columns - 1) as f64 * self.gap_x;
+        let height = self.margin_top
+            + self.rows as f64 * self.label_height
+            + (self.rows - 1) as f64 * self.gap_y;
+        if width > self.page_width + LAYOUT_TOLERANCE
+            || height > self.page_height + LAYOUT_TOLERANCE
+        {
+            return invalid("labels do not fit on the page");
+        }
+        Ok(())
+    }
+}
+
+impl Default for LabelLayout {
+    fn default() -> Self {
+        LabelLayout::AVERY_3X8
+    }
+}
+
+/// draw the lines of an address vertically centered on a label
+fn draw_label(canvas: &mut dyn Canvas, layout: &LabelLayout, x: f64, y: f64, lines: &[String]) {
+    let line_height = layout.font_size * 0.45;
+    let top = y + (layout.label_height - line_height * lines.len() as f64) / 2.0;
+    for (i, line) in lines.iter().enumerate() {
+        let baseline = top + line_height * (i as f64 + 0.8);
+        canvas.text(x + 6.0, baseline, layout.font_size, false, line);
+    }
+}
+
+/// Render address labels for the households as PDF, filling the sheets row by row.
+///
+/// Households without complete address are skipped, they can be found with
+/// Household::address().
+///
+/// # Errors
+/// ExportError::InvalidLayout if the layout is not valid, see LabelLayout::validate().
+pub fn labels_to_pdf(
+    households: &[Household],
+    layout: &LabelLayout,
+    language: Language,
+) -> Result<Vec<u8>, ExportError> {
+    layout.validate()?;
+    let mut pdf = Pdf::new(layout.page_width, layout.page_height);
+    let addresses = households
+        .iter()
+        .filter_map(|household| household.address(language));
+    for (i, address) in addresses.enumerate() {
+        let position = i % layout.labels_per_page();
+        if i > 0 && position == 0 {
+            pdf.new_page();
+        }
+        let column = position % layout.columns;
+        let row = position / layout.columns;
+        let x = layout.margin_left + column as f64 * (layout.label_width + layout.gap_x);
+        let y = layout.margin_top + row as f64 * (layout.label_height + layout.gap_y);
+        draw_label(&mut pdf, layout, x, y, &address.lines());
+    }
+    Ok(pdf.finish())
+}
+
+/// Write the households as CSV for a mail merge, one line per household.
+///
+/// The file is formatted for Excel like export::write_csv() with default options. Households
+/// without complete address have empty address fields.
+pub fn write_labels_csv<W: std::io::Write>(
+    writer: W,
+    households: &[Household],
+    language: Language,
+) -> Result<(), ExportError> {
+    let mut csv = csv_writer(writer, &CsvOptions::default())?;
+    csv.write_record(match language {
+        Language::De => [
+            "Anrede",
+            "Strasse",
+            "Hausnummer",
+            "PLZ",
+            "Ort",
+            "Land",
+            "Personen",
+        ],
+        Language::Fr => [
+            "Formule",
+            "Rue",
+            "Numéro",
+            "NPA",
+            "Lieu",
+            "Pays",
+            "Personnes",
+        ],
+        Language::It => [
+            "Saluto",
+            "Via",
+            "Numero",
+            "NPA",
+            "Località",
+            "Paese",
+            "Persone",
+        ],
+    })?;
+    for household in households {
+        let members: Vec<String> = household
+            .members
+            .iter()
+            .map(|person| person.display_name())
+            .collect();
+        let members = members.join(", ");
+        match household.address(language) {
+            Some(address) => csv.write_record([
+                address.name.as_str(),
+                address.street.as_deref().unwrap_or_default(),
+                address.house_number.as_deref().unwrap_or_default(),
+                &address.zip_code,
+                &address.town,
+                &address.country,
+                &members,
+            ])?,
+            None => csv.write_record([
+                household.salutation(language).as_str(),
+                "",
+                "",
+                "",
+                "",
+                "",
+                &members,
+            ])?,
+        }
+    }
+    csv.flush()?;
+    Ok(())
+}
+
+/// Households as CSV, see write_labels_csv()
+pub fn labels_to_csv(households: &[Household], language: Language) -> Vec<u8> {
+    let mut csv: Vec<u8> = vec![];
+    write_labels_csv(&mut csv, households, language).expect("writing to memory can not fail");
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn people() -> Vec<Person> {
+        serde_json::from_str(
+            r#"[
+                {"id": "1", "first_name": "Anna", "last_name": "Muster", "company": false,
+                 "address": "Weg 1", "zip_code": "3000", "town": "Bern",
+                 "household_key": "abc", "links": {}},
+                {"id": "2", "first_name": "Ben", "last_name": "Meier", "company": false,
+                 "address": "Weg 1", "zip_code": "3000", "town": "Bern",
+                 "household_key": "abc", "links": {}},
+                {"id": "3", "first_name": "Carla", "last_name": "Muster", "company": false,
+                 "address": " weg  1", "zip_code": "3000", "town": "Bern", "links": {}},
+                {"id": "4", "first_name": "Dario", "last_name": "Rossi", "company": false,
+                 "address": "Via 2", "zip_code": "6900", "town": "Lugano", "links": {}},
+                {"id": "5", "first_name": "Eva", "last_name": "Rossi", "company": false,
+                 "address": "Via 2", "zip_code": "6900", "town": "Lugano", "links": {}},
+                {"id": "6", "first_name": "Fritz", "last_name": "Frei", "company": false,
+                 "links": {}},
+                {"id": "1", "first_name": "Anna", "last_name": "Muster", "company": false,
+                 "household_key": "abc", "links": {}}
+            ]"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn group_households() {
+        let people = people();
+        let households = households(&people);
+        let members: Vec<Vec<&str>> = households
+            .iter()
+            .map(|h| h.members.iter().map(|p| p.id.as_str()).collect())
+            .collect();
+        assert_eq!(
+            members,
+            vec![vec!["1", "2", "3"], vec!["4", "5"], vec!["6"]]
+        );
+        assert_eq!(households[0].household_key.as_deref(), Some("abc"));
+        assert_eq!(households[1].household_key, None);
+
+        let salutations: Vec<String> = households
+            .iter()
+            .map(|h| h.salutation(Language::De))
+            .collect();
+        assert_eq!(
+            salutations,
+            vec!["Familie Muster und Meier", "Familie Rossi", "Fritz Frei"]
+        );
+        assert_eq!(households[1].salutation(Language::Fr), "Famille Rossi");
+
+        let address = households[0].address(Language::De).unwrap();
+        assert_eq!(address.street.as_deref(), Some("Weg"));
+        assert_eq!(address.house_number.as_deref(), Some("1"));
+        assert!(households[2].address(Language::De).is_none());
+    }
+
+    #[test]
+    fn labels() {
+        let people = people();
+        let households = households(&people);
+        let csv = String::from_utf8(labels_to_csv(&households, Language::De)).unwrap();
+        assert_eq!(
+            csv,
+            "\u{feff}Anrede;Strasse;Hausnummer;PLZ;Ort;Land;Personen\n\
+             Familie Muster und Meier;Weg;1;3000;Bern;CH;Anna Muster, Ben Meier, Carla Muster\n\
+             Familie Rossi;Via;2;6900;Lugano;CH;Dario Rossi, Eva Rossi\n\
+             Fritz Frei;;;;;;Fritz Frei\n"
+        );
+
+        let layout = LabelLayout {
+            columns: 1,
+            rows: 1,
+            ..LabelLayout::default()
+        };
+        let pdf = labels_to_pdf(&households, &layout, Language::De).unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+        assert!(pdf.contains("/Count 2"));
+        assert!(pdf.contains("(Familie Rossi) Tj"));
+
+        assert!(LabelLayout::AVERY_3X7.validate().is_ok());
+        let layout = LabelLayout {
+            columns: 0,
+            ..LabelLayout::default()
+        };
+        assert!(matches!(
+            labels_to_pdf(&households, &layout, Language::De),
+            Err(ExportError::InvalidLayout(_))
+        ));
+        let layout = LabelLayout {
+            rows: 9,
+            ..LabelLayout::default()
+        };
+        assert!(layout.validate().is_err());
+    }
+}
@@ -6,9 +6,47 @@ extern crate tokio;
 #[macro_use]
 extern crate serde_derive;
 extern crate cached;
+extern crate chrono;
+extern crate csv;
+extern crate qrcode;
+extern crate roxmltree;
+extern crate rusqlite;
+extern crate rust_xlsxwriter;
+extern crate serde_json;
+
+mod drawing;
+pub mod duplicates;
+pub mod export;
+pub mod graph;
+pub mod households;
+pub mod mirror;
+pub mod qrbill;
+pub mod reconciliation;
+pub mod snapshot;
+pub mod stats;
+pub mod ueberstufung;
 
 /// Module for requesting and storing of information on Midata
 pub mod midata {
+    use cached::Cached;
+    use chrono::{DateTime, NaiveDate, Utc};
+    use futures::{Stream, StreamExt};
+
+    mod api;
+    mod changes;
+    mod history;
+    mod invoices;
+    mod jsonapi;
+    mod mailing_lists;
+    mod people;
+    mod roles;
+    pub use self::api::*;
+    pub use self::changes::*;
+    pub use self::history::*;
+    pub use self::invoices::*;
+    pub use self::mailing_lists::*;
+    pub use self::people::*;
+
     #[derive(PartialEq, Debug)]
     pub enum Token {
         XUserToken(String, String),
@@ -20,6 +58,83 @@ pub mod midata {
         pub token: Token,
     }
 
+    /// Errors occurring while requesting information from midata
+    #[derive(Debug)]
+    pub enum Error {
+        /// the connection has no valid token, e.g. because the login failed
+        MissingToken,
+        /// the request failed or the response could not be deserialized
+        Request(reqwest::Error),
+        /// the token is not allowed to do the requested modification
+        Forbidden,
+        /// midata responded with an unexpected http status
+        Status(u16),
+        /// midata responded successfully but without the expected content
+        UnexpectedResponse,
+        /// the pages of a paginated response did not advance or exceeded the page limit
+        Pagination,
+        /// people can not subscribe themselves to the mailing list
+        NotOptIn,
+        /// midata rejected the modification because of invalid fields
+        Validation(Vec<FieldError>),
+        /// the role type does not exist in groups of that type, or the group type is unknown
+        InvalidRoleType {
+            role_type: String,
+            group_type: String,
+        },
+        /// the person was created, but giving it its role failed
+        RoleNotCreated {
+            person_id: String,
+            error: Box<Error>,
+        },
+    }
+
+    /// Invalid field reported by midata
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct FieldError {
+        /// name of the attribute, e.g. `email`
+        pub field: String,
+        pub message: String,
+    }
+
+    impl std::fmt::Display for Error {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            match self {
+                Error::MissingToken => write!(f, "Missing token. Something is wrong."),
+                Error::Request(e) => write!(f, "Request to midata failed: {}", e),
+                Error::Forbidden => write!(f, "Not allowed to modify this entry."),
+                Error::Status(status) => write!(f, "Midata responded with status {}", status),
+                Error::UnexpectedResponse => write!(f, "Midata responded without content."),
+                Error::Pagination => write!(f, "Midata responded with endless pages."),
+                Error::NotOptIn => write!(f, "The mailing list is not opt-in."),
+                Error::Validation(errors) => {
+                    let errors: Vec<String> = errors
+                        .iter()
+                        .map(|e| format!("{} {}", e.field, e.message))
+                        .collect();
+                    write!(f, "Invalid fields: {}", errors.join(", "))
+                }
+                Error::InvalidRoleType {
+                    role_type,
+                    group_type,
+                } => write!(f, "{} is not a role of {}", role_type, group_type),
+                Error::RoleNotCreated { person_id, error } => write!(
+                    f,
+                    "Person {} was created without role: {}",
+                    person_id, error
+                ),
+            }
+        }
+    }
+
+    impl std::error::Error for Error {}
+
+    impl From<reqwest::Error> for Error {
+        fn from(e: reqwest::Error) -> Self {
+            Error::Request(e)
+        }
+    }
+
     /// Links of people to roles
     #[derive(Deserialize, Debug, Clone)]
     struct PersonLinks {
@@ -28,6 +143,19 @@ pub mod midata {
         primary_group: String,
         /// Links to roles
         roles: Option<Vec<String>>,
+        /// Links to phone numbers
+        phone_numbers: Option<Vec<String>>,
+    }
+
+    /// Phone number of a person
+    #[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+    pub struct PhoneNumber {
+        pub id: String,
+        pub number: String,
+        /// kind of the number, e.g. "Mobil" or "Privat"
+        pub label: Option<String>,
+        /// visible to everybody who can see the person
+        pub public: Option<bool>,
     }
 
     /// Person in the midata database
@@ -44,6 +172,7 @@ pub mod midata {
         pub company: bool,
         pub email: Option<String>,
         pub gender: Option<String>,
+        pub birthday: Option<chrono::NaiveDate>,
         pub address: Option<String>,
         pub zip_code: Option<String>,
         pub town: Option<String>,
@@ -61,6 +190,9 @@ pub mod midata {
         /// not mapped. facilitated access to roles
         #[serde(default)]
         pub roles: Vec<Role>,
+        /// not mapped. phone numbers of the person
+        #[serde(default)]
+        pub phone_numbers: Vec<PhoneNumber>,
         /// not mapped. when loaded from a group, not all fields are loaded/populated. Load remaining fields using load()
         #[serde(skip)]
         is_loaded_fully: bool,
@@ -70,6 +202,9 @@ pub mod midata {
         /// not mapped. utility to check if person has a leading function in any group.
         #[serde(skip)]
         pub is_leiter: bool,
+        /// not mapped. open invoices of the person, see attach_open_invoices()
+        #[serde(skip)]
+        pub open_invoices: Vec<Invoice>,
         // NOTE: Update merge_persons if more fields are added
     }
 
@@ -79,17 +214,137 @@ pub mod midata {
         people: Option<Vec<Person>>,
         groups: Option<Vec<Group>>,
         linked: Option<Linked>,
+        people_filters: Option<Vec<PeopleFilter>>,
+        events: Option<Vec<Event>>,
+        mailing_lists: Option<Vec<MailingList>>,
+        invoices: Option<Vec<Invoice>>,
+        /// link to the next page if the response is paginated and this is not the last page
+        next_page_link: Option<String>,
     }
 
     /// Links for groups. Contains links to parent group and optionally the children groups
     #[derive(Serialize, Deserialize, Clone, Debug)]
     struct GroupLinks {
-        parent: String,
+        /// id of the parent group. Not set for the root group.
+        parent: Option<String>,
         layer_group: String,
         hierarchies: Option<Vec<String>>,
         children: Option<Vec<String>>,
     }
 
+    /// Types of groups in the pbs midata
+    #[derive(Clone, PartialEq, Eq, Hash, Debug)]
+    pub enum GroupType {
+        Bund,
+        Kantonalverband,
+        Region,
+        Abteilung,
+        Biber,
+        Woelfe,
+        Pfadi,
+        Pio,
+        Rover,
+        Pta,
+        /// other group types, holding the type as given by midata
+        Other(String),
+    }
+
+    impl GroupType {
+        /// parse the group type as given by midata, either as class name (`Group::Woelfe`) or as
+        /// label (`Wölfe`)
+        pub fn parse(group_type: &str) -> GroupType {
+            let name = group_type.trim_start_matches("Group::").to_lowercase();
+            match name.as_str() {
+                "bund" => GroupType::Bund,
+                "kantonalverband" => GroupType::Kantonalverband,
+                "region" => GroupType::Region,
+                "abteilung" => GroupType::Abteilung,
+                "biber" | "biberstufe" => GroupType::Biber,
+                "woelfe" | "wölfe" | "wolfsstufe" => GroupType::Woelfe,
+                "pfadi" | "pfadistufe" => GroupType::Pfadi,
+                "pio" | "piostufe" => GroupType::Pio,
+                "abteilungsrover" | "rover" | "roverstufe" => GroupType::Rover,
+                "pta" => GroupType::Pta,
+                _ => GroupType::Other(group_type.to_string()),
+            }
+        }
+
+        /// class name of the group type in midata, e.g. `Group::Woelfe`
+        pub fn class_name(&self) -> &str {
+            match self {
+                GroupType::Bund => "Group::Bund",
+                GroupType::Kantonalverband => "Group::Kantonalverband",
+                GroupType::Region => "Group::Region",
+                GroupType::Abteilung => "Group::Abteilung",
+                GroupType::Biber => "Group::Biber",
+                GroupType::Woelfe => "Group::Woelfe",
+                GroupType::Pfadi => "Group::Pfadi",
+                GroupType::Pio => "Group::Pio",
+                GroupType::Rover => "Group::AbteilungsRover",
+                GroupType::Pta => "Group::Pta",
+                GroupType::Other(group_type) => group_type,
+            }
+        }
+
+        /// names of the roles existing in groups of this type, e.g. `Wolf` for the Wölfe.
+        /// None if the roles of the group type are not known to this crate.
+        fn role_names(&self) -> Option<&'static [&'static str]> {
+            match self {
+                GroupType::Biber => {
+                    Some(&["Einheitsleitung", "Mitleitung", "Adressverwaltung", "Biber"])
+                }
+                GroupType::Woelfe => Some(&[
+                    "Einheitsleitung",
+                    "Mitleitung",
+                    "Adressverwaltung",
+                    "Leitwolf",
+                    "Wolf",
+                ]),
+                GroupType::Pfadi => Some(&[
+                    "Einheitsleitung",
+                    "Mitleitung",
+                    "Adressverwaltung",
+                    "Leitpfadi",
+                    "Pfadi",
+                ]),
+                GroupType::Pio => {
+                    Some(&["Einheitsleitung", "Mitleitung", "Adressverwaltung", "Pio"])
+                }
+                GroupType::Rover => {
+                    Some(&["Einheitsleitung", "Mitleitung", "Adressverwaltung", "Rover"])
+                }
+                _ => None,
+            }
+        }
+
+        /// Check if roles of the given type (e.g. `Group::Woelfe::Wolf`) exist in groups of this
+        /// type.
+        ///
+        /// # Note
+        /// The roles of the Stufen are checked against the known role types. For the other group
+        /// types only the class name is checked, which works for unknown group types only if
+        /// midata gave the class name, as the JSON:API does. No role type is allowed for unknown
+        /// group types given as label.
+        pub fn allows_role_type(&self, role_type: &str) -> bool {
+            let class_name = self.class_name();
+            let role = match role_type
+                .strip_prefix(class_name)
+                .and_then(|role| role.strip_prefix("::"))
+            {
+                Some(role)
+                    if class_name.starts_with("Group::")
+                        && !role.is_empty()
+                        && !role.contains("::") =>
+                {
+                    role
+                }
+                _ => return false,
+            };
+            self.role_names()
+                .is_none_or(|role_names| role_names.contains(&role))
+        }
+    }
+
     /// Holds information about groups loaded from midata
     #[derive(Serialize, Deserialize, Clone, Debug)]
     pub struct Group {
@@ -100,13 +355,15 @@ pub mod midata {
         pub name: String,
         short_name: Option<String>,
         email: Option<String>,
-        address: Option<String>,
-        zip_code: Option<u16>,
-        town: Option<String>,
-        country: Option<String>,
+        /// street and house number
+        pub address: Option<String>,
+        pub zip_code: Option<u16>,
+        pub town: Option<String>,
+        pub country: Option<String>,
         pbs_shortname: Option<String>,
         website: Option<String>,
-        bank_account: Option<String>,
+        /// IBAN of the group
+        pub bank_account: Option<String>,
         description: Option<String>,
         pta: Option<bool>,
         vkp: Option<bool>,
@@ -140,10 +397,106 @@ pub mod midata {
         pub id: String,
         pub role_type: String,
         pub label: Option<String>,
-        pub created_at: String,
-        pub updated_at: String,
-        pub deleted_at: Option<String>,
+        #[serde(default, deserialize_with = "deserialize_timestamp")]
+        pub created_at: Option<DateTime<Utc>>,
+        #[serde(default, deserialize_with = "deserialize_timestamp")]
+        pub updated_at: Option<DateTime<Utc>>,
+        /// time the role was deleted, only set for roles loaded with their history
+        #[serde(default, deserialize_with = "deserialize_timestamp")]
+        pub deleted_at: Option<DateTime<Utc>>,
+        /// first day of the role, if midata gives it
+        #[serde(default, deserialize_with = "deserialize_date")]
+        pub start_on: Option<NaiveDate>,
+        /// last day of the role, if it is ended or will end
+        #[serde(default, deserialize_with = "deserialize_date")]
+        pub end_on: Option<NaiveDate>,
         pub links: Option<RolesLinks>,
+        /// not mapped. id of the person having the role
+        #[serde(skip)]
+        pub person_id: Option<String>,
+    }
+
+    /// Parse a timestamp given by midata, either RFC 3339 (`2023-08-20T10:00:00+02:00`) or as
+    /// written by older versions (`2023-08-20 10:00:00 +0200`). None if empty or invalid.
+    pub fn parse_timestamp(text: &str) -> Option<DateTime<Utc>> {
+        let text = text.trim();
+        DateTime::parse_from_rfc3339(text)
+            .or_else(|_| DateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S %z"))
+            .ok()
+            .map(|timestamp| timestamp.with_timezone(&Utc))
+    }
+
+    /// midata gives missing timestamps as null or as empty string. Timestamps in an unknown
+    /// format are missing as well.
+    fn deserialize_timestamp<'de, D>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let text: Option<String> = serde::Deserialize::deserialize(deserializer)?;
+        Ok(text.as_deref().and_then(parse_timestamp))
+    }
+
+    /// dates given as null, as empty string or in an unknown format are missing
+    fn deserialize_date<'de, D>(deserializer: D) -> Result<Option<NaiveDate>, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let text: Option<String> = serde::Deserialize::deserialize(deserializer)?;
+        Ok(text
+            .as_deref()
+            .and_then(|text| NaiveDate::parse_from_str(text.trim(), "%Y-%m-%d").ok()))
+    }
+
+    /// Links of events to the groups organizing them
+    #[derive(Deserialize, Debug, Clone)]
+    struct EventLinks {
+        groups: Option<Vec<String>>,
+    }
+
+    /// Event (e.g. camp or course) in the midata database
+    #[derive(Deserialize, Debug, Clone)]
+    pub struct Event {
+        pub id: String,
+        /// url to page about that event
+        pub href: Option<String>,
+        /// type of the event, e.g. `Event::Camp`
+        #[serde(rename = "type")]
+        pub event_type: Option<String>,
+        pub name: String,
+        pub description: Option<String>,
+        pub location: Option<String>,
+        /// links to the organizing groups
+        links: Option<EventLinks>,
+    }
+
+    impl Event {
+        /// ids of the groups organizing the event
+        pub fn group_ids(&self) -> Vec<u16> {
+            self.links
+                .as_ref()
+                .and_then(|links| links.groups.as_ref())
+                .map(|groups| groups.iter().filter_map(|id| id.parse().ok()).collect())
+                .unwrap_or_default()
+        }
+    }
+
+    /// Kinds of entries to search for, see MidataConnection::search()
+    #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+    pub enum SearchKind {
+        People,
+        Groups,
+        Events,
+    }
+
+    /// Entry found by MidataConnection::search()
+    ///
+    /// # Note
+    /// The entries are not fully loaded. Use Person::load() or Group::load() to get the details.
+    #[derive(Debug, Clone)]
+    pub enum SearchHit {
+        Person(Person),
+        Group(Group),
+        Event(Event),
     }
 
     /// generic container for links loaded from midata
@@ -151,23 +504,177 @@ pub mod midata {
     struct Linked {
         groups: Option<Vec<Group>>,
         roles: Option<Vec<Role>>,
+        phone_numbers: Option<Vec<PhoneNumber>>,
+        subscriptions: Option<Vec<Subscription>>,
+        invoice_items: Option<Vec<InvoiceItem>>,
+        payments: Option<Vec<Payment>>,
+    }
+
+    /// Range of people to load when listing the people of a group
+    #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+    pub enum PeopleRange {
+        /// only the direct members of the group
+        #[default]
+        Group,
+        /// members of the group and of all groups below it within the same layer
+        Layer,
+        /// members of the group and of all groups below it
+        Deep,
+    }
+
+    impl PeopleRange {
+        fn as_param(&self) -> &'static str {
+            match self {
+                PeopleRange::Group => "group",
+                PeopleRange::Layer => "layer",
+                PeopleRange::Deep => "deep",
+            }
+        }
+    }
+
+    /// Options for loading the people of a group
+    ///
+    /// # Note
+    /// If a saved filter is given with filter_id, midata ignores all other options.
+    #[derive(Clone, PartialEq, Eq, Hash, Debug, Default)]
+    pub struct PeopleQuery {
+        /// which groups below the requested group are included
+        pub range: PeopleRange,
+        /// only load people having one of these role types (e.g. `Group::Pfadi::Leitpfadi`).
+        /// All role types are loaded if empty.
+        pub role_types: Vec<String>,
+        /// also load people with roles in archived groups
+        pub include_archived: bool,
+        /// id of a saved people filter of the group, see load_people_filters()
+        pub filter_id: Option<u32>,
+        /// only load people with an active qualification of one of these kinds (ids)
+        pub qualification_kinds: Vec<u32>,
+        /// only load people tagged with one of these tags
+        pub tags: Vec<String>,
+        /// only load people at least this many years old
+        pub min_age: Option<u8>,
+        /// only load people at most this many years old
+        pub max_age: Option<u8>,
+        /// only load people with a role which ended between these days (inclusive), e.g. to
+        /// find the people who left in a year
+        pub ended_roles: Option<(NaiveDate, NaiveDate)>,
+    }
+
+    impl PeopleQuery {
+        fn params(&self) -> Vec<(String, String)> {
+            if let Some(filter_id) = self.filter_id {
+                return vec![("filter_id".to_string(), filter_id.to_string())];
+            }
+
+            let mut params = vec![("range".to_string(), self.range.as_param().to_string())];
+            for role_type in &self.role_types {
+                params.push((
+                    "filters[role][role_types][]".to_string(),
+                    role_type.to_owned(),
+                ));
+            }
+            if self.include_archived {
+                params.push((
+                    "filters[role][include_archived]".to_string(),
+                    "true".to_string(),
+                ));
+            }
+            if !self.qualification_kinds.is_empty() {
+                let ids: Vec<String> = self
+                    .qualification_kinds
+                    .iter()
+                    .map(|id| id.to_string())
+                    .collect();
+                params.push((
+                    "filters[qualification][qualification_kind_ids]".to_string(),
+                    ids.join("-"),
+                ));
+                params.push((
+                    "filters[qualification][validity]".to_string(),
+                    "active".to_string(),
+                ));
+            }
+            if let Some((from, to)) = self.ended_roles {
+                params.push(("filters[role][kind]".to_string(), "deleted".to_string()));
+                params.push(("filters[role][start_at]".to_string(), from.to_string()));
+                params.push(("filters[role][finish_at]".to_string(), to.to_string()));
+            }
+            if !self.tags.is_empty() {
+                params.push(("filters[tag][names]".to_string(), self.tags.join(",")));
+            }
+            // midata compares the age exclusively
+            let age_constraints = [
+                ("greater", self.min_age.map(|age| age as i16 - 1)),
+                ("smaller", self.max_age.map(|age| age as i16 + 1)),
+            ];
+            for (i, (constraint, age)) in age_constraints.iter().enumerate() {
+                if let Some(age) = age {
+                    params.push((
+                        format!("filters[attributes][{}][key]", i),
+                        "years".to_string(),
+                    ));
+                    params.push((
+                        format!("filters[attributes][{}][constraint]", i),
+                        constraint.to_string(),
+                    ));
+                    params.push((
+                        format!("filters[attributes][{}][value]", i),
+                        age.to_string(),
+                    ));
+                }
+            }
+            params
+        }
+    }
+
+    /// People filter saved in a group
+    #[derive(Deserialize, Debug, Clone)]
+    pub struct PeopleFilter {
+        pub id: String,
+        pub name: String,
+        /// not mapped. id of the group the filter was loaded from
+        #[serde(skip)]
+        group: u16,
+    }
+
+    impl PeopleFilter {
+        /// query to load the people matching this filter. Fails if midata returned an id
+        /// that is not numeric.
+        pub fn query(&self) -> Result<PeopleQuery, Error> {
+            Ok(PeopleQuery {
+                filter_id: Some(jsonapi::numeric_id(&self.id)?),
+                ..Default::default()
+            })
+        }
+
+        /// load the people matching this filter
+        pub fn load_people(&self, connection: &MidataConnection) -> Result<Vec<Person>, Error> {
+            Ok(connection.load_people_of_group_with(self.group, self.query()?))
+        }
     }
 
     /// generic structure to hold different request types to midata
-    #[derive(Clone, PartialEq, Eq, Hash, Copy)]
+    #[derive(Clone, PartialEq, Eq, Hash)]
     enum Request {
         Groups(u16),
         PeopleOfGroup(u16),
+        PeopleOfGroupWith(u16, PeopleQuery),
         People(u16, u32),
+        PeopleFilters(u16),
+        Search(String),
+        MailingLists(u16),
+        MailingList(u16, u32),
+        MailingListRecipients(u16, u32),
+        Invoices(u16),
     }
 
     /// authenticate using an application token
     /// # NOTE:
     /// You will not get all the details about the groups/people when using this authentication method
     pub fn connection_with_application_token(token: String) -> MidataConnection {
-        return MidataConnection {
+        MidataConnection {
             token: Token::XToken(token),
-        };
+        }
     }
 
     /// authenticate using email and password
@@ -177,16 +684,14 @@ pub mod midata {
         };
 
         mc.login(email, password);
-        return mc;
+        mc
     }
 
     /// authenticate using email and password
     pub fn connection_with_user_token(email: String, token: String) -> MidataConnection {
-        let mc = MidataConnection {
+        MidataConnection {
             token: Token::XUserToken(email, token),
-        };
-
-        return mc;
+        }
     }
 
     impl Group {
@@ -210,11 +715,11 @@ pub mod midata {
         /// This does not fully load the persons. Use @ref get_persons_with_details
         /// if the details of the people are needed.
         pub fn get_persons<'a>(&'a mut self, connection: &MidataConnection) -> &'a Vec<Person> {
-            if !self.people.is_some() {
+            if self.people.is_none() {
                 let id: u16 = self.id.parse().unwrap();
                 self.people.replace(connection.load_people_of_group(id));
             }
-            return self.people.as_ref().unwrap();
+            self.people.as_ref().unwrap()
         }
 
         /// get members of a group. Load the members with full details if they are not loaded yet.
@@ -243,13 +748,137 @@ pub mod midata {
 
             self.people.replace(result);
 
-            return self.people.as_ref().unwrap();
+            self.people.as_ref().unwrap()
+        }
+
+        /// get the type of the group
+        pub fn group_type(&self) -> GroupType {
+            GroupType::parse(&self.group_type)
+        }
+
+        /// id of the parent group. None for the root group or if the links are not loaded.
+        pub fn parent_id(&self) -> Option<&str> {
+            self.links.as_ref()?.parent.as_deref()
+        }
+
+        /// id of the layer group the group belongs to, if the links are loaded
+        pub fn layer_group_id(&self) -> Option<&str> {
+            Some(self.links.as_ref()?.layer_group.as_str())
+        }
+
+        /// get the ancestors of the group, starting with the root group and ending with the parent.
+        ///
+        /// # Note
+        /// The ancestors are not fully loaded. If the group was not loaded with load_group(),
+        /// the hierarchy is loaded from midata.
+        pub fn ancestors(&self, connection: &MidataConnection) -> Vec<Group> {
+            match &self.hierarchies {
+                Some(hierarchies) => hierarchies
+                    .iter()
+                    .filter(|group| group.id != self.id)
+                    .cloned()
+                    .collect(),
+                None => connection
+                    .load_group(self.id.parse().unwrap())
+                    .ancestors(connection),
+            }
+        }
+
+        /// get the parent group. None for the root group.
+        ///
+        /// # Note
+        /// The parent is not fully loaded.
+        pub fn parent(&self, connection: &MidataConnection) -> Option<Group> {
+            self.ancestors(connection).pop()
+        }
+
+        /// get the layer group the group belongs to. This is the group itself if it is a layer.
+        ///
+        /// # Note
+        /// The layer group is not fully loaded unless it is the group itself.
+        pub fn layer_group(&self, connection: &MidataConnection) -> Group {
+            let layer_id = match &self.links {
+                Some(links) => links.layer_group.clone(),
+                None => {
+                    let group = connection.load_group(self.id.parse().unwrap());
+                    return group.layer_group(connection);
+                }
+            };
+            if layer_id == self.id {
+                return self.clone();
+            }
+            match self
+                .ancestors(connection)
+                .into_iter()
+                .find(|group| group.id == layer_id)
+            {
+                Some(group) => group,
+                None => connection.load_group(layer_id.parse().unwrap()),
+            }
+        }
+
+        /// get the names of the ancestors and the group, starting with the root group.
+        ///
+        /// # Note
+        /// Only the name of the group itself is returned if the group was not loaded with
+        /// load_group().
+        pub fn path_names(&self) -> Vec<&str> {
+            let mut names: Vec<&str> = self
+                .hierarchies
+                .iter()
+                .flatten()
+                .filter(|group| group.id != self.id)
+                .map(|group| group.name.as_str())
+                .collect();
+            names.push(&self.name);
+            names
+        }
+
+        /// get the names of the ancestors and the group separated by " / ",
+        /// e.g. "Pfadi Bern / Pfadi X / Meute Y". See path_names().
+        pub fn path_string(&self) -> String {
+            self.path_names().join(" / ")
+        }
+    }
+
+    impl Role {
+        /// Check if the role is a participant role (Biber, Wolf, Leitwolf, Pfadi, Leitpfadi, Pio),
+        /// given either as label or as class name.
+        pub fn is_tn(&self) -> bool {
+            let role = self.role_type.rsplit("::").next();
+            ["Biber", "Wolf", "Leitwolf", "Pfadi", "Leitpfadi", "Pio"]
+                .iter()
+                .any(|&tn_role| role == Some(tn_role))
+        }
+
+        /// load the group the role belongs to. None if the role has no links.
+        pub fn group(&self, connection: &MidataConnection) -> Option<Group> {
+            let id: u16 = self.links.as_ref()?.group.parse().ok()?;
+            Some(connection.load_group(id))
+        }
+    }
+
+    /// Check if roles make someone a leader: people with at least one role but no participant
+    /// role (Role::is_tn()) are leaders. Used for Person::is_leiter and all statistics.
+    pub fn is_leader<'a, I>(roles: I) -> bool
+    where
+        I: IntoIterator<Item = &'a Role>,
+    {
+        let mut has_roles = false;
+        for role in roles {
+            if role.is_tn() {
+                return false;
+            }
+            has_roles = true;
         }
+        has_roles
     }
 
     fn merge_option_if_needed<T>(option_a: &mut Option<T>, option_b: Option<T>) {
-        if option_a.is_none() && option_b.is_some() {
-            option_a.replace(option_b.unwrap());
+        if option_a.is_none() {
+            if let Some(b) = option_b {
+                option_a.replace(b);
+            }
         }
     }
 
@@ -257,14 +886,26 @@ pub mod midata {
         option_a: Option<Vec<T>>,
         option_b: Option<Vec<T>>,
     ) -> Option<Vec<T>> {
-        if option_a.is_none() && option_b.is_some() {
-            return option_b;
-        } else if option_a.is_some() && option_b.is_some() {
-            let mut vec1: Vec<T> = option_a.unwrap();
-            vec1.append(&mut option_b.unwrap());
-            return Some(vec1);
+        match (option_a, option_b) {
+            (None, b) => b,
+            (Some(mut a), Some(mut b)) => {
+                a.append(&mut b);
+                Some(a)
+            }
+            (a, None) => a,
         }
-        return option_a;
+    }
+
+    /// first and last name followed by the scout's name, e.g. "Anna Muster / Fuchs"
+    pub fn display_name(
+        first_name: Option<&str>,
+        last_name: Option<&str>,
+        nickname: Option<&str>,
+    ) -> String {
+        let mut names: Vec<&str> = [first_name, last_name].iter().flatten().copied().collect();
+        let nickname = nickname.map(|nickname| format!("/ {}", nickname));
+        names.extend(nickname.as_deref());
+        names.join(" ")
     }
 
     impl Person {
@@ -279,20 +920,62 @@ pub mod midata {
             }
         }
 
-        /// Check if the person has a leader role in any group.
+        /// Roles of the person active today, see Role::is_active_on(). Ended roles are only
+        /// included in roles if they were loaded with MidataConnection::load_role_history().
+        pub fn current_roles(&self) -> impl Iterator<Item = &Role> {
+            let today = chrono::Local::now().date_naive();
+            self.roles
+                .iter()
+                .filter(move |role| role.is_active_on(today))
+        }
+
+        /// Check if the person has a participant role in any group. False for people without
+        /// current roles.
         ///
         /// # Note:
-        /// This checks for the roles Biber, Wolf, Leitwolf, Pfadi, Leitpfadi, Pio
+        /// This checks the current roles for Biber, Wolf, Leitwolf, Pfadi, Leitpfadi, Pio, given
+        /// either as label or as class name (e.g. `Group::Pfadi::Leitpfadi`).
         pub fn is_tn(&self) -> bool {
-            assert_ne!(self.roles.len(), 0);
-            vec!["Biber", "Wolf", "Leitwolf", "Pfadi", "Leitpfadi", "Pio"]
+            self.current_roles().any(Role::is_tn)
+        }
+
+        /// Check if the person is a leader, see is_leader(). Only current roles are considered.
+        pub fn is_leader(&self) -> bool {
+            is_leader(self.current_roles())
+        }
+
+        /// first and last name followed by the scout's name, e.g. "Anna Muster / Fuchs"
+        pub fn display_name(&self) -> String {
+            display_name(
+                self.first_name.as_deref(),
+                self.last_name.as_deref(),
+                self.nickname.as_deref(),
+            )
+        }
+
+        /// id of the primary group of the person, if known
+        pub fn primary_group_id(&self) -> Option<&str> {
+            Some(self.links.primary_group.as_str()).filter(|id| !id.is_empty())
+        }
+
+        /// Request the details of the person from the group of its first role (or its primary
+        /// group) instead of the group the person was listed in.
+        fn request_from_own_group(&mut self) {
+            if let Some(gid) = self
+                .roles
                 .iter()
-                .any(|&tn_role| self.roles.iter().any(|r| r.role_type == tn_role))
+                .filter_map(|role| role.links.as_ref())
+                .find_map(|links| links.group.parse().ok())
+                .or_else(|| self.links.primary_group.parse().ok())
+            {
+                self.requested_by_group = gid;
+            }
         }
 
         fn merge_persons(&mut self, mut person: Person) {
             merge_option_if_needed(&mut self.email, person.email);
             merge_option_if_needed(&mut self.gender, person.gender);
+            merge_option_if_needed(&mut self.birthday, person.birthday);
             merge_option_if_needed(&mut self.address, person.address);
             merge_option_if_needed(&mut self.zip_code, person.zip_code);
             merge_option_if_needed(&mut self.town, person.town);
@@ -302,19 +985,93 @@ pub mod midata {
 
             self.links.roles =
                 merge_option_vec_if_needed(self.links.roles.clone(), person.links.roles);
+            self.links.phone_numbers = merge_option_vec_if_needed(
+                self.links.phone_numbers.clone(),
+                person.links.phone_numbers,
+            );
             self.roles.append(&mut person.roles);
+            for phone_number in person.phone_numbers {
+                if !self.phone_numbers.contains(&phone_number) {
+                    self.phone_numbers.push(phone_number);
+                }
+            }
+            self.open_invoices.append(&mut person.open_invoices);
             self.is_loaded_fully = self.is_loaded_fully || person.is_loaded_fully;
             self.is_leiter = self.is_leiter || person.is_leiter;
         }
     }
 
+    /// Number of participants and leaders of a group
+    #[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+    pub struct MemberCounts {
+        /// people with a participant role, see Person::is_tn()
+        pub tn: usize,
+        /// people with roles but no participant role, see Person::is_leader()
+        pub leiter: usize,
+        /// all people of the group
+        pub total: usize,
+    }
+
+    impl MemberCounts {
+        /// count the people of a group, e.g. loaded with MidataConnection::load_people_of_group()
+        pub fn of(people: &[Person]) -> MemberCounts {
+            MemberCounts {
+                tn: people.iter().filter(|person| person.is_tn()).count(),
+                leiter: people.iter().filter(|person| person.is_leader()).count(),
+                total: people.len(),
+            }
+        }
+    }
+
+    /// Collect the people of the responses and attach the linked roles and phone numbers to them.
+    fn link_roles(responses: Vec<Response>, is_loaded_fully: bool) -> Vec<Person> {
+        let mut persons: Vec<Person> = vec![];
+        for r in responses {
+            if let Some(response_people) = r.people {
+                for mut person in response_people {
+                    let mut person_roles: Vec<Role> = vec![];
+                    if let Some(roles) = &person.links.roles {
+                        if let Some(result_roles) = &r.linked {
+                            if let Some(eff_roles) = &result_roles.roles {
+                                for role_string in roles {
+                                    for role in eff_roles {
+                                        if role_string == &role.id {
+                                            let mut role = role.clone();
+                                            role.person_id = Some(person.id.clone());
+                                            person_roles.push(role);
+                                            break;
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    person.roles = person_roles;
+                    if let (Some(ids), Some(linked)) = (&person.links.phone_numbers, &r.linked) {
+                        person.phone_numbers = linked
+                            .phone_numbers
+                            .iter()
+                            .flatten()
+                            .filter(|phone_number| ids.contains(&phone_number.id))
+                            .cloned()
+                            .collect();
+                    }
+                    person.is_leiter = person.is_leader();
+                    person.is_loaded_fully = is_loaded_fully;
+                    persons.push(person);
+                }
+            }
+        }
+        persons
+    }
+
     impl MidataConnection {
         /// Load a group
         ///
         /// # Arguments
         /// id: id of the group to load
         pub fn load_group(&self, id: u16) -> Group {
-            return self.load_groups(vec![id]).pop().unwrap();
+            self.load_groups(vec![id]).pop().unwrap()
         }
 
         /// Load multiple groups
@@ -323,7 +1080,7 @@ pub mod midata {
         /// ids: ids of the group to load
         pub fn load_groups(&self, ids: Vec<u16>) -> Vec<Group> {
             let responses: Vec<Response> =
-                self.load(ids.into_iter().map(|id| Request::Groups(id)).collect());
+                self.load(ids.into_iter().map(Request::Groups).collect());
             let mut groups: Vec<Group> = vec![];
 
             // iterate over responses
@@ -403,58 +1160,101 @@ pub mod midata {
             let mut output: Vec<Person> = vec![];
 
             for person in loaded_persons {
-                if output.len() > 0 && person.id == output.last().unwrap().id {
-                    let last = output.last_mut().unwrap();
-                    last.merge_persons(person);
-                } else {
-                    output.push(person);
+                match output.last_mut() {
+                    Some(last) if last.id == person.id => last.merge_persons(person),
+                    _ => output.push(person),
                 }
             }
 
-            return output;
+            output
         }
 
         pub fn load_people_of_group(&self, id: u16) -> Vec<Person> {
-            return self.load_people_of_groups(vec![id]);
+            self.load_people_of_groups(vec![id])
         }
 
         pub fn load_people_of_groups(&self, ids: Vec<u16>) -> Vec<Person> {
-            let responses: Vec<Response> = self.load(
-                ids.into_iter()
-                    .map(|id| Request::PeopleOfGroup(id))
-                    .collect(),
-            );
-            let mut persons: Vec<Person> = vec![];
-            for r in responses {
-                if let Some(response_people) = r.people {
-                    for mut person in response_people {
-                        let mut person_roles: Vec<Role> = vec![];
-                        if let Some(roles) = &person.links.roles {
-                            if let Some(result_roles) = &r.linked {
-                                if let Some(eff_roles) = &result_roles.roles {
-                                    for role_string in roles {
-                                        for role in eff_roles {
-                                            if role_string == &role.id {
-                                                person_roles.push(role.clone());
-                                                break;
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                        person.roles = person_roles;
-                        person.is_leiter = !person.is_tn();
-                        person.is_loaded_fully = false;
-                        persons.push(person);
-                    }
+            let responses: Vec<Response> =
+                self.load(ids.into_iter().map(Request::PeopleOfGroup).collect());
+            link_roles(responses, false)
+        }
+
+        /// Load the people of groups like load_people_of_groups(), but return an error instead
+        /// of panicking if a page could not be loaded.
+        pub fn try_load_people_of_groups(&self, ids: Vec<u16>) -> Result<Vec<Person>, Error> {
+            let responses: Vec<Response> =
+                self.try_load(ids.into_iter().map(Request::PeopleOfGroup).collect())?;
+            Ok(link_roles(responses, false))
+        }
+
+        /// Load the people of a group, optionally including the people of all groups below it.
+        ///
+        /// # Arguments
+        /// id: id of the group to load the people from
+        /// query: range and role type filters to apply
+        ///
+        /// # Note
+        /// The people are not fully loaded. Each person remembers the group of its first role (or its
+        /// primary group) so that Person::load() requests the details from a group the person is
+        /// actually member of.
+        pub fn load_people_of_group_with(&self, id: u16, query: PeopleQuery) -> Vec<Person> {
+            let responses: Vec<Response> = self.load(vec![Request::PeopleOfGroupWith(id, query)]);
+            let mut persons = link_roles(responses, false);
+            persons.iter_mut().for_each(Person::request_from_own_group);
+            persons
+        }
+
+        /// Load the people filters saved in a group
+        ///
+        /// # Arguments
+        /// id: id of the group to load the filters from
+        pub fn load_people_filters(&self, id: u16) -> Vec<PeopleFilter> {
+            let mut filters: Vec<PeopleFilter> = vec![];
+            for r in self.load(vec![Request::PeopleFilters(id)]) {
+                for mut filter in r.people_filters.unwrap_or_default() {
+                    filter.group = id;
+                    filters.push(filter);
                 }
             }
-            persons
+            filters
+        }
+
+        /// Search people, groups and events by name
+        ///
+        /// # Arguments
+        /// query: text to search for, e.g. the name or nickname of a person
+        /// kinds: kinds of entries to return
+        ///
+        /// # Note
+        /// The people found remember the group of their first role (or their primary group) so
+        /// that Person::load() can be used without knowing their group.
+        pub fn search(&self, query: &str, kinds: &[SearchKind]) -> Vec<SearchHit> {
+            let mut responses = self.load(vec![Request::Search(query.to_string())]);
+            let mut groups: Vec<Group> = vec![];
+            let mut events: Vec<Event> = vec![];
+            for r in &mut responses {
+                groups.append(&mut r.groups.take().unwrap_or_default());
+                events.append(&mut r.events.take().unwrap_or_default());
+            }
+
+            let mut hits: Vec<SearchHit> = vec![];
+            if kinds.contains(&SearchKind::People) {
+                for mut person in link_roles(responses, false) {
+                    person.request_from_own_group();
+                    hits.push(SearchHit::Person(person));
+                }
+            }
+            if kinds.contains(&SearchKind::Groups) {
+                hits.extend(groups.into_iter().map(SearchHit::Group));
+            }
+            if kinds.contains(&SearchKind::Events) {
+                hits.extend(events.into_iter().map(SearchHit::Event));
+            }
+            hits
         }
 
         pub fn load_person(&self, gid: u16, id: u32) -> Person {
-            return self.load_people(vec![(gid, id)]).pop().unwrap();
+            self.load_people(vec![(gid, id)]).pop().unwrap()
         }
 
         pub fn load_people(&self, ids: Vec<(u16, u32)>) -> Vec<Person> {
@@ -463,33 +1263,7 @@ pub mod midata {
                     .map(|ids| Request::People(ids.0, ids.1))
                     .collect(),
             );
-            let mut persons: Vec<Person> = vec![];
-            for r in responses {
-                if let Some(response_people) = r.people {
-                    for mut person in response_people {
-                        let mut person_roles: Vec<Role> = vec![];
-                        if let Some(roles) = &person.links.roles {
-                            if let Some(result_roles) = &r.linked {
-                                if let Some(eff_roles) = &result_roles.roles {
-                                    for role_string in roles {
-                                        for role in eff_roles {
-                                            if role_string == &role.id {
-                                                person_roles.push(role.clone());
-                                                break;
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                        person.roles = person_roles;
-                        person.is_leiter = !person.is_tn();
-                        person.is_loaded_fully = true;
-                        persons.push(person);
-                    }
-                }
-            }
-            persons
+            link_roles(responses, true)
         }
 
         fn login(&mut self, email: String, password: String) {
@@ -515,10 +1289,7 @@ pub mod midata {
                     .json::<Response>()
                     .await
                     .expect("Could not deserialize to json");
-                return response
-                    .people?
-                    .pop()?
-                    .authentication_token;
+                response.people?.pop()?.authentication_token
             }
             let token = get_token(&email, &password);
             if let Some(token) = token {
@@ -528,91 +1299,366 @@ pub mod midata {
             }
         }
 
-        #[tokio::main]
-        async fn load(&self, requests: Vec<Request>) -> Vec<Response> {
-            let client = reqwest::Client::new();
-
-            #[cached::proc_macro::cached(size = 1000, convert = "{request}", key = "Request")]
-            async fn _load_int(
-                client: &reqwest::Client,
-                request: Request,
-                credentials: &MidataConnection,
-            ) -> Response {
-                let url = match request {
-                    Request::Groups(id) => {
-                        format!("https://db.scout.ch/de/groups/{}", id)
-                    }
-                    Request::PeopleOfGroup(id) => {
-                        format!("https://db.scout.ch/de/groups/{}/people", id)
-                    }
-                    Request::People(idg, idp) => {
-                        format!("https://db.scout.ch/de/groups/{}/people/{}", idg, idp)
-                    }
-                };
-                let url = reqwest::Url::parse(&url).expect("Failed to parse url");
+        /// Stream the people of a group page by page.
+        ///
+        /// # Arguments
+        /// id: id of the group to load the people from
+        /// query: range and role type filters to apply
+        ///
+        /// # Note
+        /// In contrast to load_people_of_group_with() the pages are only requested while the
+        /// stream is polled, so large groups can be processed without holding all people in
+        /// memory. The pages are not cached. The stream has to be polled within a tokio runtime.
+        pub fn stream_people_of_group_with(
+            &self,
+            id: u16,
+            query: PeopleQuery,
+        ) -> impl Stream<Item = Result<Person, Error>> + '_ {
+            self.stream_people(Request::PeopleOfGroupWith(id, query))
+        }
 
-                let mut headers = reqwest::header::HeaderMap::new();
+        /// Stream the direct members of a group page by page. See stream_people_of_group_with().
+        pub fn stream_people_of_group(
+            &self,
+            id: u16,
+        ) -> impl Stream<Item = Result<Person, Error>> + '_ {
+            self.stream_people(Request::PeopleOfGroup(id))
+        }
 
-                match &credentials.token {
-                    Token::XToken(token) => {
-                        headers.insert(
-                            "X-Token",
-                            reqwest::header::HeaderValue::from_str(&token).unwrap(),
-                        );
-                    }
-                    Token::XUserToken(user, token) => {
-                        headers.insert(
-                            "X-User-Token",
-                            reqwest::header::HeaderValue::from_str(&token).unwrap(),
-                        );
-                        headers.insert(
-                            "X-User-Email",
-                            reqwest::header::HeaderValue::from_str(&user).unwrap(),
-                        );
-                    }
-                    Token::XNone => {
-                        panic!("Missing token. Something is wrong.");
+        fn stream_people(
+            &self,
+            request: Request,
+        ) -> impl Stream<Item = Result<Person, Error>> + '_ {
+            let client = reqwest::Client::new();
+            futures::stream::unfold(
+                Some((1, None)),
+                move |state: Option<(u32, Option<String>)>| {
+                    let client = client.clone();
+                    let request = request.clone();
+                    async move {
+                        let (page, previous_link) = state?;
+                        match fetch_page(&client, request.clone(), page, self).await {
+                            Ok(response) => {
+                                let next_page =
+                                    match next_page(page, previous_link.as_ref(), &response) {
+                                        Ok(next_page) => next_page,
+                                        Err(e) => {
+                                            return Some((
+                                                futures::stream::iter(vec![Err(e)]),
+                                                None,
+                                            ))
+                                        }
+                                    };
+                                let next_state = next_page
+                                    .map(|next_page| (next_page, response.next_page_link.clone()));
+                                let mut persons = link_roles(vec![response], false);
+                                if let Request::PeopleOfGroupWith(..) = request {
+                                    persons.iter_mut().for_each(Person::request_from_own_group);
+                                }
+                                let persons: Vec<Result<Person, Error>> =
+                                    persons.into_iter().map(Ok).collect();
+                                Some((futures::stream::iter(persons), next_state))
+                            }
+                            Err(e) => Some((futures::stream::iter(vec![Err(e)]), None)),
+                        }
                     }
-                }
-                headers.insert(
-                    "Accept",
-                    reqwest::header::HeaderValue::from_static(&"application/json"),
-                );
+                },
+            )
+            .flatten()
+        }
 
-                let body = client.get(url).headers(headers).send();
+        /// headers authenticating requests with the token of the connection
+        fn headers(&self) -> Result<reqwest::header::HeaderMap, Error> {
+            let mut headers = reqwest::header::HeaderMap::new();
 
-                let body = body.await.expect("Error loading body");
-                let mut response = body
-                    .json::<Response>()
-                    .await
-                    .expect("Could not deserialize to json");
-                if let Request::PeopleOfGroup(id) = request {
-                    if let Some(people) = &mut response.people {
-                        for person in people {
-                            person.requested_by_group = id;
-                        }
-                    }
+            match &self.token {
+                Token::XToken(token) => {
+                    headers.insert(
+                        "X-Token",
+                        reqwest::header::HeaderValue::from_str(token).unwrap(),
+                    );
+                }
+                Token::XUserToken(user, token) => {
+                    headers.insert(
+                        "X-User-Token",
+                        reqwest::header::HeaderValue::from_str(token).unwrap(),
+                    );
+                    headers.insert(
+                        "X-User-Email",
+                        reqwest::header::HeaderValue::from_str(user).unwrap(),
+                    );
+                }
+                Token::XNone => {
+                    return Err(Error::MissingToken);
                 }
-                response
             }
+            headers.insert(
+                "Accept",
+                reqwest::header::HeaderValue::from_static("application/json"),
+            );
+            Ok(headers)
+        }
+
+        /// Send a modification to midata.
+        ///
+        /// # Note
+        /// All cached responses are dropped afterwards since they might be outdated.
+        #[tokio::main]
+        async fn send(
+            &self,
+            method: reqwest::Method,
+            url: &str,
+            params: Vec<(String, String)>,
+        ) -> Result<(), Error> {
+            let url = reqwest::Url::parse(url).expect("Failed to parse url");
+            let response = reqwest::Client::new()
+                .request(method, url)
+                .headers(self.headers()?)
+                .form(&params)
+                .send()
+                .await?;
+            LOAD_PAGE.lock().await.cache_clear();
+            check_status(response.status())
+        }
+
+        /// Load the requests and all their pages.
+        ///
+        /// # Panics
+        /// Panics if a page could not be loaded. Use try_load() to handle the error.
+        fn load(&self, requests: Vec<Request>) -> Vec<Response> {
+            self.try_load(requests).expect("Error loading body")
+        }
+
+        /// Load the requests and all their pages. Fails if any page could not be loaded.
+        #[tokio::main]
+        async fn try_load(&self, requests: Vec<Request>) -> Result<Vec<Response>, Error> {
+            let client = reqwest::Client::new();
 
             let mut remining_requests = requests.as_slice();
             let mut responses: Vec<Response> = vec![];
-            while remining_requests.len() > 0 {
+            while !remining_requests.is_empty() {
                 let index = std::cmp::min(100, remining_requests.len());
                 let split_req = remining_requests.split_at(index);
                 remining_requests = split_req.1;
 
                 let t_requests: Vec<_> = split_req
                     .0
-                    .into_iter()
-                    .map(|req| _load_int(&client, req.to_owned(), self))
+                    .iter()
+                    .map(|req| load_all_pages(&client, req.to_owned(), self))
                     .collect();
-                let mut t_responses: Vec<Response> = futures::future::join_all(t_requests).await;
-                responses.append(&mut t_responses);
+                for t_responses in futures::future::join_all(t_requests).await {
+                    responses.extend(t_responses?);
+                }
             }
 
-            responses
+            Ok(responses)
+        }
+    }
+
+    /// map unsuccessful responses of midata to errors
+    fn check_status(status: reqwest::StatusCode) -> Result<(), Error> {
+        match status {
+            status if status.is_success() => Ok(()),
+            reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN => {
+                Err(Error::Forbidden)
+            }
+            status => Err(Error::Status(status.as_u16())),
+        }
+    }
+
+    /// Load a request and all following pages if the response is paginated.
+    async fn load_all_pages(
+        client: &reqwest::Client,
+        request: Request,
+        credentials: &MidataConnection,
+    ) -> Result<Vec<Response>, Error> {
+        let mut responses: Vec<Response> = vec![];
+        let mut page = 1;
+        loop {
+            let response = load_page(client, request.clone(), page, credentials).await?;
+            let previous_link = responses.last().and_then(|r| r.next_page_link.as_ref());
+            let next = next_page(page, previous_link, &response)?;
+            responses.push(response);
+            match next {
+                Some(next) => page = next,
+                None => break,
+            }
+        }
+        Ok(responses)
+    }
+
+    /// Maximal number of pages loaded for a single request
+    const MAX_PAGES: u32 = 1000;
+
+    /// Number of the page following the response. Fails if midata links the same next page
+    /// again or the response has more than MAX_PAGES pages.
+    fn next_page(
+        page: u32,
+        previous_link: Option<&String>,
+        response: &Response,
+    ) -> Result<Option<u32>, Error> {
+        match &response.next_page_link {
+            None => Ok(None),
+            Some(link) if Some(link) == previous_link || page >= MAX_PAGES => {
+                Err(Error::Pagination)
+            }
+            Some(_) => Ok(Some(page + 1)),
+        }
+    }
+
+    /// Load a single page of a request. Responses are cached, see fetch_page() for the
+    /// uncached variant.
+    #[cached::proc_macro::cached(
+        size = 1000,
+        convert = "{(request.clone(), page)}",
+        key = "(Request, u32)",
+        result = true
+    )]
+    async fn load_page(
+        client: &reqwest::Client,
+        request: Request,
+        page: u32,
+        credentials: &MidataConnection,
+    ) -> Result<Response, Error> {
+        fetch_page(client, request, page, credentials).await
+    }
+
+    /// Load a single page of a request without caching the response.
+    async fn fetch_page(
+        client: &reqwest::Client,
+        request: Request,
+        page: u32,
+        credentials: &MidataConnection,
+    ) -> Result<Response, Error> {
+        let (url, mut params) = match &request {
+            Request::Groups(id) => (format!("https://db.scout.ch/de/groups/{}", id), vec![]),
+            Request::PeopleOfGroup(id) => (
+                format!("https://db.scout.ch/de/groups/{}/people", id),
+                vec![],
+            ),
+            Request::PeopleOfGroupWith(id, query) => (
+                format!("https://db.scout.ch/de/groups/{}/people", id),
+                query.params(),
+            ),
+            Request::People(idg, idp) => (
+                format!("https://db.scout.ch/de/groups/{}/people/{}", idg, idp),
+                vec![],
+            ),
+            Request::PeopleFilters(id) => (
+                format!("https://db.scout.ch/de/groups/{}/people_filters", id),
+                vec![],
+            ),
+            Request::MailingLists(id) => (
+                format!("https://db.scout.ch/de/groups/{}/mailing_lists", id),
+                vec![],
+            ),
+            Request::MailingList(idg, id) => (
+                format!("https://db.scout.ch/de/groups/{}/mailing_lists/{}", idg, id),
+                vec![],
+            ),
+            Request::MailingListRecipients(idg, id) => (
+                format!(
+                    "https://db.scout.ch/de/groups/{}/mailing_lists/{}/subscriptions",
+                    idg, id
+                ),
+                vec![],
+            ),
+            Request::Invoices(id) => (
+                format!("https://db.scout.ch/de/groups/{}/invoices", id),
+                vec![],
+            ),
+            Request::Search(query) => (
+                "https://db.scout.ch/de/full".to_string(),
+                vec![("q".to_string(), query.to_owned())],
+            ),
+        };
+        if page > 1 {
+            params.push(("page".to_string(), page.to_string()));
+        }
+        let mut url = reqwest::Url::parse(&url).expect("Failed to parse url");
+        if !params.is_empty() {
+            url.query_pairs_mut().extend_pairs(params);
+        }
+
+        let body = client
+            .get(url)
+            .headers(credentials.headers()?)
+            .send()
+            .await?;
+        let mut response = body.json::<Response>().await?;
+        if let Request::PeopleOfGroup(id) | Request::PeopleOfGroupWith(id, _) = request {
+            if let Some(people) = &mut response.people {
+                for person in people {
+                    person.requested_by_group = id;
+                }
+            }
+        }
+        Ok(response)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn page(next_page_link: Option<&str>) -> Response {
+            let json = match next_page_link {
+                Some(link) => format!(r#"{{"people": [], "next_page_link": "{}"}}"#, link),
+                None => r#"{"people": []}"#.to_string(),
+            };
+            serde_json::from_str(&json).unwrap()
+        }
+
+        #[test]
+        fn next_page_link() {
+            let link = "https://db.scout.ch/de/groups/1/people.json?page=2";
+            assert_eq!(page(Some(link)).next_page_link.as_deref(), Some(link));
+            assert_eq!(page(None).next_page_link, None);
+        }
+
+        #[test]
+        fn next_page_follows_link() {
+            let response = page(Some("people.json?page=2"));
+            assert_eq!(next_page(1, None, &response).unwrap(), Some(2));
+            let previous = "people.json?page=2".to_string();
+            let response = page(Some("people.json?page=3"));
+            assert_eq!(next_page(2, Some(&previous), &response).unwrap(), Some(3));
+            assert_eq!(next_page(3, Some(&previous), &page(None)).unwrap(), None);
+        }
+
+        #[test]
+        fn next_page_rejects_repeated_link() {
+            let previous = "people.json?page=2".to_string();
+            let response = page(Some("people.json?page=2"));
+            assert!(matches!(
+                next_page(2, Some(&previous), &response),
+                Err(Error::Pagination)
+            ));
+        }
+
+        #[test]
+        fn next_page_stops_after_max_pages() {
+            let response = page(Some("people.json?page=next"));
+            assert_eq!(
+                next_page(MAX_PAGES - 1, None, &response).unwrap(),
+                Some(MAX_PAGES)
+            );
+            assert!(matches!(
+                next_page(MAX_PAGES, None, &response),
+                Err(Error::Pagination)
+            ));
+            assert_eq!(next_page(MAX_PAGES, None, &page(None)).unwrap(), None);
+        }
+
+        #[test]
+        fn people_filter_query() {
+            let mut filter = PeopleFilter {
+                id: "42".to_string(),
+                name: "Leitende".to_string(),
+                group: 1,
+            };
+            assert_eq!(filter.query().unwrap().filter_id, Some(42));
+            filter.id = "leitende".to_string();
+            assert!(matches!(filter.query(), Err(Error::UnexpectedResponse)));
         }
     }
 }
@@ -633,6 +1679,94 @@ mod tests {
         }
     }
 
+    #[test]
+    fn group_path() {
+        let mc = login();
+        let group = mc.load_group(6497);
+        println!("{}", group.path_string());
+        println!("{:?}", group.parent(&mc).map(|g| g.name));
+        println!("{}", group.layer_group(&mc).name);
+        assert_eq!(
+            group.ancestors(&mc).len() + 1,
+            group.path_string().split(" / ").count()
+        );
+    }
+
+    #[test]
+    fn load_mailing_lists() {
+        let mc = login();
+        for list in mc.load_mailing_lists(6497) {
+            println!("{:?}", mc.load_subscriptions(&list).unwrap());
+            println!("{:?}", mc.load_recipients(&list).unwrap());
+        }
+    }
+
+    #[test]
+    fn load_open_invoices() {
+        let mc = login();
+        let query = crate::midata::InvoiceQuery {
+            states: vec![crate::midata::InvoiceState::Issued],
+            ..Default::default()
+        };
+        let invoices = mc.load_invoices(6497, &query);
+        let mut people = mc.load_people_of_group(6497);
+        crate::midata::attach_open_invoices(&mut people, &invoices);
+        for p in people {
+            println!("{:?}: {:?}", p.id, p.open_invoices);
+        }
+    }
+
+    #[test]
+    fn member_counts() {
+        use crate::midata::{MemberCounts, Person};
+        let people: Vec<Person> = serde_json::from_str(
+            r#"[
+                {"id": "10", "company": false, "links": {},
+                 "roles": [{"id": "1", "role_type": "Wolf", "created_at": "",
+                            "updated_at": ""}]},
+                {"id": "11", "company": false, "links": {},
+                 "roles": [{"id": "2", "role_type": "Leitwolf", "created_at": "",
+                            "updated_at": ""},
+                           {"id": "3", "role_type": "Coach", "created_at": "",
+                            "updated_at": ""}]},
+                {"id": "12", "company": false, "links": {},
+                 "roles": [{"id": "4", "role_type": "Einheitsleitung", "created_at": "",
+                            "updated_at": ""}]},
+                {"id": "13", "company": false, "links": {}}
+            ]"#,
+        )
+        .unwrap();
+        assert!(!people[3].is_tn());
+        assert!(!people[3].is_leader());
+        assert_eq!(
+            MemberCounts::of(&people),
+            MemberCounts {
+                tn: 2,
+                leiter: 1,
+                total: 4
+            }
+        );
+    }
+
+    #[test]
+    fn role_types_of_group_types() {
+        use crate::midata::GroupType;
+
+        assert_eq!(GroupType::parse("Group::Woelfe"), GroupType::Woelfe);
+        assert_eq!(GroupType::parse("Wölfe"), GroupType::Woelfe);
+        assert!(GroupType::Woelfe.allows_role_type("Group::Woelfe::Wolf"));
+        assert!(!GroupType::Woelfe.allows_role_type("Group::Pfadi::Pfadi"));
+        assert!(!GroupType::Pfadi.allows_role_type("Group::Pfadi::Sub::Pfadi"));
+        assert!(!GroupType::Woelfe.allows_role_type("Group::Woelfe::Foo"));
+        assert!(!GroupType::Woelfe.allows_role_type("Group::Woelfe::"));
+        assert!(GroupType::Woelfe.allows_role_type("Group::Woelfe::Einheitsleitung"));
+        assert!(GroupType::Rover.allows_role_type("Group::AbteilungsRover::Rover"));
+        assert!(GroupType::Abteilung.allows_role_type("Group::Abteilung::Coach"));
+        assert!(!GroupType::Other("Elternrat".to_string()).allows_role_type("Group::X::Y"));
+        assert!(GroupType::parse("Group::Elternrat").allows_role_type("Group::Elternrat::Mitglied"));
+        assert!(!GroupType::parse("Group::Gremium").allows_role_type("Group::Pfadi::Pfadi"));
+    }
+
     #[test]
     fn load_persons_of_group() {
         let mc = login();
@@ -642,6 +1776,50 @@ mod tests {
         }
     }
 
+    #[test]
+    fn load_persons_of_layer() {
+        let mc = login();
+        let query = crate::midata::PeopleQuery {
+            range: crate::midata::PeopleRange::Deep,
+            ..Default::default()
+        };
+        let res = mc.load_people_of_group_with(6497, query);
+        for r in res {
+            println!("{:?}", r);
+        }
+    }
+
+    #[tokio::test]
+    async fn stream_persons_of_group() {
+        use futures::StreamExt;
+
+        let mc = login();
+        let mut stream = Box::pin(mc.stream_people_of_group(5763));
+        while let Some(r) = stream.next().await {
+            println!("{:?}", r.unwrap());
+        }
+    }
+
+    #[test]
+    fn load_persons_of_filters() {
+        let mc = login();
+        for filter in mc.load_people_filters(6497) {
+            println!("{}: {:?}", filter.name, filter.load_people(&mc));
+        }
+    }
+
+    #[test]
+    fn search() {
+        let mc = login();
+        let kinds = [
+            crate::midata::SearchKind::People,
+            crate::midata::SearchKind::Groups,
+        ];
+        for hit in mc.search("Muster", &kinds) {
+            println!("{:?}", hit);
+        }
+    }
+
     #[test]
     fn load_persons() {
         let mc = login();
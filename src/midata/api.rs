@@ -0,0 +1,433 @@
+use super::jsonapi::Resource;
+use super::{
+    parse_timestamp, Error, Event, Group, MidataConnection, Person, PhoneNumber, Role, RolesLinks,
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde_json::{Map, Value};
+
+/// Query for the JSON:API of hitobito (`/api/people`, `/api/groups`, `/api/events` and
+/// `/api/roles`).
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Default)]
+pub struct ApiQuery {
+    include: Vec<String>,
+    fields: Vec<(String, Vec<String>)>,
+    filters: Vec<(String, String)>,
+    sort: Vec<String>,
+    page_number: Option<u32>,
+    page_size: Option<u32>,
+}
+
+impl ApiQuery {
+    /// query without any options, loading all resources with all fields
+    pub fn new() -> ApiQuery {
+        ApiQuery::default()
+    }
+
+    /// also load the related resources, e.g. `roles` of people
+    pub fn include(mut self, relationship: &str) -> ApiQuery {
+        self.include.push(relationship.to_string());
+        self
+    }
+
+    /// only load the given fields of resources of a type, e.g. `people`.
+    ///
+    /// # Note
+    /// Relationships are fields as well. Add them if they are included, e.g. `roles`.
+    pub fn fields(mut self, resource_type: &str, fields: &[&str]) -> ApiQuery {
+        self.fields.push((
+            resource_type.to_string(),
+            fields.iter().map(|field| field.to_string()).collect(),
+        ));
+        self
+    }
+
+    /// only load resources whose attribute equals the value (`filter[attribute]=value`).
+    /// Several values can be given separated by commas.
+    pub fn filter(mut self, attribute: &str, value: &str) -> ApiQuery {
+        self.filters
+            .push((format!("filter[{}]", attribute), value.to_string()));
+        self
+    }
+
+    /// only load resources whose attribute matches the value using an operator like `gte`, `lt`
+    /// or `match` (`filter[attribute][operator]=value`)
+    pub fn filter_with(mut self, attribute: &str, operator: &str, value: &str) -> ApiQuery {
+        self.filters.push((
+            format!("filter[{}][{}]", attribute, operator),
+            value.to_string(),
+        ));
+        self
+    }
+
+    /// sort ascending by the attribute. Further calls sort by further attributes.
+    pub fn sort(mut self, attribute: &str) -> ApiQuery {
+        self.sort.push(attribute.to_string());
+        self
+    }
+
+    /// sort descending by the attribute
+    pub fn sort_descending(mut self, attribute: &str) -> ApiQuery {
+        self.sort.push(format!("-{}", attribute));
+        self
+    }
+
+    /// only load the given page (starting with 1) instead of all pages
+    pub fn page(mut self, number: u32) -> ApiQuery {
+        self.page_number = Some(number);
+        self
+    }
+
+    /// number of resources per page
+    pub fn page_size(mut self, size: u32) -> ApiQuery {
+        self.page_size = Some(size);
+        self
+    }
+
+    pub(super) fn params(&self) -> Vec<(String, String)> {
+        let mut params: Vec<(String, String)> = vec![];
+        if !self.include.is_empty() {
+            params.push(("include".to_string(), self.include.join(",")));
+        }
+        for (resource_type, fields) in &self.fields {
+            params.push((format!("fields[{}]", resource_type), fields.join(",")));
+        }
+        params.extend(self.filters.iter().cloned());
+        if !self.sort.is_empty() {
+            params.push(("sort".to_string(), self.sort.join(",")));
+        }
+        if let Some(number) = self.page_number {
+            params.push(("page[number]".to_string(), number.to_string()));
+        }
+        if let Some(size) = self.page_size {
+            params.push(("page[size]".to_string(), size.to_string()));
+        }
+        params
+    }
+}
+
+/// id given as string or number
+fn id_of(value: Option<&Value>) -> Option<String> {
+    match value? {
+        Value::String(id) => Some(id.clone()),
+        Value::Number(id) => Some(id.to_string()),
+        _ => None,
+    }
+}
+
+fn text_of(resource: &Resource, name: &str) -> Option<String> {
+    resource
+        .attributes
+        .get(name)
+        .and_then(Value::as_str)
+        .map(str::to_string)
+}
+
+fn timestamp_of(resource: &Resource, name: &str) -> Option<DateTime<Utc>> {
+    parse_timestamp(resource.attributes.get(name)?.as_str()?)
+}
+
+fn date_of(resource: &Resource, name: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(resource.attributes.get(name)?.as_str()?, "%Y-%m-%d").ok()
+}
+
+/// id of the related resource given either as relationship or as attribute `<name>_id`
+pub(super) fn related_id(resource: &Resource, name: &str) -> Option<String> {
+    resource
+        .related_ids(name)
+        .pop()
+        .or_else(|| id_of(resource.attributes.get(&format!("{}_id", name))))
+}
+
+fn find<'a>(resources: &'a [Resource], resource_type: &str, id: &str) -> Option<&'a Resource> {
+    resources
+        .iter()
+        .find(|r| r.resource_type == resource_type && r.id.as_deref() == Some(id))
+}
+
+/// attributes of the resource with its id, prepared to be deserialized into a struct
+fn object_of(resource: &Resource) -> Map<String, Value> {
+    let mut object = resource.attributes.clone();
+    object.remove("links");
+    object.insert(
+        "id".to_string(),
+        Value::String(resource.id.clone().unwrap_or_default()),
+    );
+    object
+}
+
+/// Map a role resource. The role type is the class name, e.g. `Group::Pfadi::Leitpfadi`.
+pub(super) fn to_role(resource: &Resource, included: &[Resource]) -> Role {
+    let links = related_id(resource, "group").map(|group| RolesLinks {
+        layer_group: find(included, "groups", &group)
+            .and_then(|group| related_id(group, "layer_group"))
+            .unwrap_or_default(),
+        group,
+    });
+    Role {
+        id: resource.id.clone().unwrap_or_default(),
+        role_type: text_of(resource, "type").unwrap_or_default(),
+        label: text_of(resource, "label"),
+        created_at: timestamp_of(resource, "created_at"),
+        updated_at: timestamp_of(resource, "updated_at"),
+        deleted_at: timestamp_of(resource, "deleted_at"),
+        start_on: date_of(resource, "start_on"),
+        end_on: date_of(resource, "end_on"),
+        links,
+        person_id: related_id(resource, "person"),
+    }
+}
+
+pub(super) fn to_person(resource: &Resource, included: &[Resource]) -> Option<Person> {
+    let mut object = object_of(resource);
+    object
+        .entry("company".to_string())
+        .or_insert(Value::Bool(false));
+    if let Some(Value::Number(zip_code)) = object.get("zip_code") {
+        let zip_code = zip_code.to_string();
+        object.insert("zip_code".to_string(), Value::String(zip_code));
+    }
+    let mut links = Map::new();
+    if let Some(primary_group) = related_id(resource, "primary_group") {
+        links.insert("primary_group".to_string(), Value::String(primary_group));
+    }
+    let role_ids = resource.related_ids("roles");
+    links.insert(
+        "roles".to_string(),
+        Value::Array(role_ids.iter().cloned().map(Value::String).collect()),
+    );
+    let phone_number_ids = resource.related_ids("phone_numbers");
+    links.insert(
+        "phone_numbers".to_string(),
+        Value::Array(
+            phone_number_ids
+                .iter()
+                .cloned()
+                .map(Value::String)
+                .collect(),
+        ),
+    );
+    object.insert("links".to_string(), Value::Object(links));
+
+    let mut person: Person = serde_json::from_value(Value::Object(object)).ok()?;
+    person.roles = role_ids
+        .iter()
+        .filter_map(|id| find(included, "roles", id))
+        .map(|role| Role {
+            person_id: Some(person.id.clone()),
+            ..to_role(role, included)
+        })
+        .collect();
+    person.phone_numbers = phone_number_ids
+        .iter()
+        .filter_map(|id| find(included, "phone_numbers", id))
+        .filter_map(|phone_number| {
+            Some(PhoneNumber {
+                id: phone_number.id.clone()?,
+                number: text_of(phone_number, "number")?,
+                label: text_of(phone_number, "label"),
+                public: phone_number
+                    .attributes
+                    .get("public")
+                    .and_then(Value::as_bool),
+            })
+        })
+        .collect();
+    person.is_leiter = person.is_leader();
+    person.request_from_own_group();
+    Some(person)
+}
+
+pub(super) fn to_group(resource: &Resource) -> Option<Group> {
+    let mut object = object_of(resource);
+    let group_type = object.remove("type").unwrap_or_default();
+    object.insert(
+        "group_type".to_string(),
+        match group_type {
+            Value::Null => Value::String(String::new()),
+            group_type => group_type,
+        },
+    );
+    object
+        .entry("name".to_string())
+        .or_insert(Value::String(String::new()));
+    if let Some(Value::String(zip_code)) = object.get("zip_code") {
+        let zip_code = zip_code.parse::<u16>().map(Value::from).unwrap_or_default();
+        object.insert("zip_code".to_string(), zip_code);
+    }
+    if let Some(layer_group) = related_id(resource, "layer_group") {
+        let mut links = Map::new();
+        links.insert(
+            "parent".to_string(),
+            related_id(resource, "parent")
+                .map(Value::String)
+                .unwrap_or_default(),
+        );
+        links.insert("layer_group".to_string(), Value::String(layer_group));
+        object.insert("links".to_string(), Value::Object(links));
+    }
+    serde_json::from_value(Value::Object(object)).ok()
+}
+
+fn to_event(resource: &Resource) -> Option<Event> {
+    let mut object = object_of(resource);
+    object
+        .entry("name".to_string())
+        .or_insert(Value::String(String::new()));
+    let mut group_ids = resource.related_ids("groups");
+    if group_ids.is_empty() {
+        if let Some(Value::Array(ids)) = resource.attributes.get("group_ids") {
+            group_ids = ids.iter().filter_map(|id| id_of(Some(id))).collect();
+        }
+    }
+    let mut links = Map::new();
+    links.insert(
+        "groups".to_string(),
+        Value::Array(group_ids.into_iter().map(Value::String).collect()),
+    );
+    object.insert("links".to_string(), Value::Object(links));
+    serde_json::from_value(Value::Object(object)).ok()
+}
+
+/// Map all resources. Fails if any of them can not be mapped.
+pub(super) fn map_all<T, F>(resources: &[Resource], map: F) -> Result<Vec<T>, Error>
+where
+    F: Fn(&Resource) -> Option<T>,
+{
+    resources
+        .iter()
+        .map(|resource| map(resource).ok_or(Error::UnexpectedResponse))
+        .collect()
+}
+
+impl MidataConnection {
+    /// Load people using the JSON:API.
+    ///
+    /// # Arguments
+    /// query: the included relationships, fields, filters, sorting and page to load
+    ///
+    /// # Note
+    /// Included roles and phone numbers are attached to the people. In contrast to the other loaders, the role
+    /// type of these roles is the class name, e.g. `Group::Pfadi::Leitpfadi`. The layer group of
+    /// a role is only known if its group is included as well.
+    ///
+    /// # Errors
+    /// Error::UnexpectedResponse if any of the resources can not be mapped, e.g. because an
+    /// attribute has an unexpected type. No resources are returned in that case.
+    pub fn query_people(&self, query: &ApiQuery) -> Result<Vec<Person>, Error> {
+        let (people, included) = self.query_resources("people", query)?;
+        map_all(&people, |person| to_person(person, &included))
+    }
+
+    /// Load groups using the JSON:API. See query_people().
+    ///
+    /// # Note
+    /// The groups are not fully loaded: their ancestors and children are loaded from midata when
+    /// needed.
+    pub fn query_groups(&self, query: &ApiQuery) -> Result<Vec<Group>, Error> {
+        let (groups, _) = self.query_resources("groups", query)?;
+        map_all(&groups, to_group)
+    }
+
+    /// Load roles using the JSON:API. See query_people().
+    pub fn query_roles(&self, query: &ApiQuery) -> Result<Vec<Role>, Error> {
+        let (roles, included) = self.query_resources("roles", query)?;
+        Ok(roles.iter().map(|role| to_role(role, &included)).collect())
+    }
+
+    /// Load events using the JSON:API. See query_people().
+    pub fn query_events(&self, query: &ApiQuery) -> Result<Vec<Event>, Error> {
+        let (events, _) = self.query_resources("events", query)?;
+        map_all(&events, to_event)
+    }
+
+    pub(super) fn query_resources(
+        &self,
+        path: &str,
+        query: &ApiQuery,
+    ) -> Result<(Vec<Resource>, Vec<Resource>), Error> {
+        self.load_resources(path, &query.params(), query.page_number.is_none())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn params() {
+        let query = ApiQuery::new()
+            .include("roles")
+            .fields("people", &["first_name", "roles"])
+            .filter("id", "1,2")
+            .filter_with("updated_at", "gte", "2024-01-01")
+            .sort("last_name")
+            .sort_descending("updated_at")
+            .page(2)
+            .page_size(50);
+        let params = query.params();
+        let params: Vec<(&str, &str)> = params
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .collect();
+        assert_eq!(
+            params,
+            vec![
+                ("include", "roles"),
+                ("fields[people]", "first_name,roles"),
+                ("filter[id]", "1,2"),
+                ("filter[updated_at][gte]", "2024-01-01"),
+                ("sort", "last_name,-updated_at"),
+                ("page[number]", "2"),
+                ("page[size]", "50"),
+            ]
+        );
+    }
+
+    #[test]
+    fn people_with_roles() {
+        let document: Vec<Resource> = serde_json::from_str(
+            r#"[
+                {"id": "42", "type": "people",
+                 "attributes": {"first_name": "Anna", "nickname": "Fuchs", "zip_code": 3000,
+                                "birthday": "2010-05-01", "primary_group_id": 3},
+                 "relationships": {"roles": {"data": [{"type": "roles", "id": "7"}]}}},
+                {"id": "7", "type": "roles",
+                 "attributes": {"type": "Group::Pfadi::Leitpfadi", "group_id": 3,
+                                "created_at": "2023-08-20T10:00:00+02:00"}},
+                {"id": "3", "type": "groups",
+                 "attributes": {"type": "Group::Pfadi", "name": "Trupp Falke", "zip_code": "3000",
+                                "parent_id": 2, "layer_group_id": 1}}
+            ]"#,
+        )
+        .unwrap();
+
+        let person = to_person(&document[0], &document[1..]).unwrap();
+        assert_eq!(person.id, "42");
+        assert_eq!(person.nickname.as_deref(), Some("Fuchs"));
+        assert_eq!(person.zip_code.as_deref(), Some("3000"));
+        assert_eq!(person.roles.len(), 1);
+        assert_eq!(person.roles[0].role_type, "Group::Pfadi::Leitpfadi");
+        let links = person.roles[0].links.as_ref().unwrap();
+        assert_eq!(
+            (links.group.as_str(), links.layer_group.as_str()),
+            ("3", "1")
+        );
+        assert!(person.is_tn());
+        assert!(!person.is_leiter);
+
+        let group = to_group(&document[2]).unwrap();
+        assert_eq!(group.name, "Trupp Falke");
+        assert_eq!(group.zip_code, Some(3000));
+        assert_eq!(group.group_type(), super::super::GroupType::Pfadi);
+
+        let mut invalid = document[2].clone();
+        invalid
+            .attributes
+            .insert("name".to_string(), Value::Bool(true));
+        assert!(map_all(&document[2..], to_group).is_ok());
+        assert!(matches!(
+            map_all(&[document[2].clone(), invalid], to_group),
+            Err(Error::UnexpectedResponse)
+        ));
+    }
+}
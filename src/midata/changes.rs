@@ -0,0 +1,374 @@
+use super::api::{map_all, related_id, to_group, to_person, to_role, ApiQuery};
+use super::jsonapi::Resource;
+use super::{parse_timestamp, Error, Group, MidataConnection, Person, Role};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+
+/// changes in this time before the end of a sync are loaded again by the next sync, as the clock
+/// of midata may differ from the local one
+const SYNC_OVERLAP_MINUTES: i64 = 5;
+
+/// number of ids filtered for in one request
+const IDS_PER_REQUEST: usize = 100;
+
+/// Part of midata to track changes of
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub enum SyncScope {
+    /// all people, groups and roles readable with the token
+    Everything,
+    /// the groups of a layer (e.g. an Abteilung), their roles and the people having them
+    Layer(u16),
+}
+
+/// How a record changed since the last sync
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ChangeKind {
+    Created,
+    Updated,
+    Deleted,
+}
+
+/// Changed record together with the kind of change
+#[derive(Clone, Debug)]
+pub struct Change<T> {
+    pub kind: ChangeKind,
+    pub record: T,
+}
+
+/// Changes since a point in time, see MidataConnection::changes_since()
+#[derive(Clone, Debug)]
+pub struct Changes {
+    pub people: Vec<Change<Person>>,
+    pub groups: Vec<Change<Group>>,
+    /// changed roles. The person having the role is given by Role::person_id. Ended roles are
+    /// reported as deleted.
+    pub roles: Vec<Change<Role>>,
+    /// ids of the people which were in the scope at the last sync but are not anymore, either
+    /// because they were deleted or because they lost their roles in the scope
+    pub deleted_people: Vec<String>,
+    /// ids of the groups which were in the scope at the last sync but are not anymore
+    pub deleted_groups: Vec<String>,
+    /// ids of all people in the scope
+    pub people_ids: Vec<String>,
+    /// ids of all groups in the scope
+    pub group_ids: Vec<String>,
+    /// time until which the changes are complete. Changes after it are part of the next sync.
+    pub until: DateTime<Utc>,
+}
+
+impl Changes {
+    /// true if nothing changed
+    pub fn is_empty(&self) -> bool {
+        self.people.is_empty()
+            && self.groups.is_empty()
+            && self.roles.is_empty()
+            && self.deleted_people.is_empty()
+            && self.deleted_groups.is_empty()
+    }
+}
+
+/// Position of a downstream system in the history of midata.
+///
+/// The cursor is stored between syncs, e.g.
+/// 1. read the cursor with SyncCursor::read()
+/// 2. get the changes with MidataConnection::changes_since(&cursor)
+/// 3. apply the changes downstream
+/// 4. advance() the cursor and write() it
+///
+/// If applying the changes fails, the cursor is not advanced and the same changes are loaded
+/// again by the next sync.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct SyncCursor {
+    pub scope: SyncScope,
+    /// all changes before this time are synced
+    pub synced_until: DateTime<Utc>,
+    /// ids of the people in the scope at the last sync, to find deleted people
+    #[serde(default)]
+    pub people_ids: Vec<String>,
+    /// ids of the groups in the scope at the last sync, to find deleted groups
+    #[serde(default)]
+    pub group_ids: Vec<String>,
+}
+
+impl SyncCursor {
+    /// cursor of a system which synced everything in the scope up to the given time
+    pub fn new(scope: SyncScope, synced_until: DateTime<Utc>) -> SyncCursor {
+        SyncCursor {
+            scope,
+            synced_until,
+            people_ids: vec![],
+            group_ids: vec![],
+        }
+    }
+
+    /// mark the changes as synced
+    pub fn advance(&mut self, changes: &Changes) {
+        self.synced_until = changes.until;
+        self.people_ids = changes.people_ids.clone();
+        self.group_ids = changes.group_ids.clone();
+    }
+
+    /// read a cursor written by write()
+    pub fn read<P: AsRef<std::path::Path>>(path: P) -> std::io::Result<SyncCursor> {
+        let json = std::fs::read_to_string(path)?;
+        serde_json::from_str(&json)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    }
+
+    /// write the cursor as JSON to a file
+    pub fn write<P: AsRef<std::path::Path>>(&self, path: P) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(self).expect("a cursor can always be serialized");
+        std::fs::write(path, json)
+    }
+}
+
+fn timestamp(resource: &Resource, name: &str) -> Option<DateTime<Utc>> {
+    parse_timestamp(resource.attributes.get(name)?.as_str()?)
+}
+
+/// the kind of change of a resource changed since the given time
+fn kind_of(resource: &Resource, since: DateTime<Utc>) -> ChangeKind {
+    if resource
+        .attributes
+        .get("deleted_at")
+        .is_some_and(|deleted_at| !deleted_at.is_null())
+    {
+        ChangeKind::Deleted
+    } else if timestamp(resource, "created_at").is_some_and(|created_at| created_at >= since) {
+        ChangeKind::Created
+    } else {
+        ChangeKind::Updated
+    }
+}
+
+/// the kind of change of a role. Roles which ended until the given time are deleted.
+fn role_kind_of(resource: &Resource, since: DateTime<Utc>, until: DateTime<Utc>) -> ChangeKind {
+    let end_on = resource
+        .attributes
+        .get("end_on")
+        .and_then(|end_on| end_on.as_str())
+        .and_then(|end_on| NaiveDate::parse_from_str(end_on, "%Y-%m-%d").ok());
+    if end_on.is_some_and(|end_on| end_on <= until.date_naive()) {
+        ChangeKind::Deleted
+    } else {
+        kind_of(resource, since)
+    }
+}
+
+fn ids_of(resources: &[Resource]) -> Vec<String> {
+    resources.iter().filter_map(|r| r.id.clone()).collect()
+}
+
+/// ids in the previous list which are not in the current one
+fn removed_ids(previous: &[String], current: &[String]) -> Vec<String> {
+    let current: std::collections::HashSet<&String> = current.iter().collect();
+    previous
+        .iter()
+        .filter(|id| !current.contains(id))
+        .cloned()
+        .collect()
+}
+
+/// add the resources which are not in the list yet
+fn append_new(resources: &mut Vec<Resource>, more: Vec<Resource>) {
+    for resource in more {
+        if !resources.iter().any(|r| r.id == resource.id) {
+            resources.push(resource);
+        }
+    }
+}
+
+impl MidataConnection {
+    /// Load the people, groups and roles created, updated or deleted since the last sync.
+    ///
+    /// # Arguments
+    /// cursor: the scope, the time of the last sync and the people and groups known by then
+    ///
+    /// # Note
+    /// The changes are found with the `updated_at` filter of the JSON:API. With the scope
+    /// Layer, people who got a role in the layer are included even if the person itself did not
+    /// change, other people are included if they have a role in the layer and changed.
+    /// Deleted people and groups are found by comparing the ids in the scope with the ones of the
+    /// cursor. Roles are deleted if midata lists them with `deleted_at` or they ended.
+    ///
+    /// The changes are complete until a few minutes before they were requested, to account for
+    /// differences of the clocks. The changes after that time are loaded again by the next sync.
+    pub fn changes_since(&self, cursor: &SyncCursor) -> Result<Changes, Error> {
+        let since = cursor.synced_until;
+        let until = (Utc::now() - Duration::minutes(SYNC_OVERLAP_MINUTES)).max(since);
+        let since_param = since.to_rfc3339();
+        let changed = |query: ApiQuery| query.filter_with("updated_at", "gte", &since_param);
+
+        let group_query = match cursor.scope {
+            SyncScope::Everything => ApiQuery::new(),
+            SyncScope::Layer(id) => ApiQuery::new().filter("layer_group_id", &id.to_string()),
+        };
+        let (groups, _) = self.query_resources("groups", &changed(group_query.clone()))?;
+        let group_ids = ids_of(
+            &self
+                .query_resources("groups", &group_query.fields("groups", &["id"]))?
+                .0,
+        );
+
+        let role_query = match cursor.scope {
+            SyncScope::Everything => ApiQuery::new(),
+            SyncScope::Layer(_) => ApiQuery::new().filter("group_id", &group_ids.join(",")),
+        };
+        let (mut roles, mut roles_groups) =
+            self.query_resources("roles", &changed(role_query.clone().include("group")))?;
+        let ended_query = role_query
+            .clone()
+            .include("group")
+            .filter_with("end_on", "gte", &since.date_naive().to_string())
+            .filter_with("end_on", "lte", &until.date_naive().to_string());
+        let (ended, ended_groups) = self.query_resources("roles", &ended_query)?;
+        append_new(&mut roles, ended);
+        append_new(&mut roles_groups, ended_groups);
+
+        let people_query = ApiQuery::new().include("phone_numbers");
+        let (people_ids, mut people, mut phone_numbers) = match cursor.scope {
+            SyncScope::Everything => {
+                let query = ApiQuery::new().fields("people", &["id"]);
+                let people_ids = ids_of(&self.query_resources("people", &query)?.0);
+                let (people, phone_numbers) =
+                    self.query_resources("people", &changed(people_query.clone()))?;
+                (people_ids, people, phone_numbers)
+            }
+            SyncScope::Layer(_) => {
+                let (scope_roles, _) = self.query_resources("roles", &role_query)?;
+                let mut people_ids: Vec<String> = scope_roles
+                    .iter()
+                    .filter_map(|role| related_id(role, "person"))
+                    .collect();
+                people_ids.sort();
+                people_ids.dedup();
+                let mut people = vec![];
+                let mut phone_numbers = vec![];
+                for ids in people_ids.chunks(IDS_PER_REQUEST) {
+                    let query = changed(people_query.clone().filter("id", &ids.join(",")));
+                    let (more, more_phone_numbers) = self.query_resources("people", &query)?;
+                    append_new(&mut people, more);
+                    append_new(&mut phone_numbers, more_phone_numbers);
+                }
+                (people_ids, people, phone_numbers)
+            }
+        };
+
+        // people who got or lost a role, even if the person itself did not change
+        let mut missing: Vec<String> = roles
+            .iter()
+            .filter_map(|role| related_id(role, "person"))
+            .filter(|id| !people.iter().any(|person| person.id.as_ref() == Some(id)))
+            .collect();
+        missing.sort();
+        missing.dedup();
+        for ids in missing.chunks(IDS_PER_REQUEST) {
+            let query = people_query.clone().filter("id", &ids.join(","));
+            let (more, more_phone_numbers) = self.query_resources("people", &query)?;
+            append_new(&mut people, more);
+            append_new(&mut phone_numbers, more_phone_numbers);
+        }
+
+        Ok(Changes {
+            people: map_all(&people, |resource| {
+                Some(Change {
+                    kind: kind_of(resource, since),
+                    record: to_person(resource, &phone_numbers)?,
+                })
+            })?,
+            groups: map_all(&groups, |resource| {
+                Some(Change {
+                    kind: kind_of(resource, since),
+                    record: to_group(resource)?,
+                })
+            })?,
+            roles: roles
+                .iter()
+                .map(|resource| Change {
+                    kind: role_kind_of(resource, since, until),
+                    record: to_role(resource, &roles_groups),
+                })
+                .collect(),
+            deleted_people: removed_ids(&cursor.people_ids, &people_ids),
+            deleted_groups: removed_ids(&cursor.group_ids, &group_ids),
+            people_ids,
+            group_ids,
+            until,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kinds() {
+        let resources: Vec<Resource> = serde_json::from_str(
+            r#"[
+                {"id": "1", "type": "roles", "attributes": {
+                    "created_at": "2024-03-01T10:00:00+01:00", "deleted_at": null}},
+                {"id": "2", "type": "roles", "attributes": {
+                    "created_at": "2024-02-29T10:00:00+01:00"}},
+                {"id": "3", "type": "roles", "attributes": {
+                    "created_at": "2024-03-01T10:00:00+01:00",
+                    "deleted_at": "2024-03-02T10:00:00+01:00"}}
+            ]"#,
+        )
+        .unwrap();
+        let since = DateTime::parse_from_rfc3339("2024-03-01T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let kinds: Vec<ChangeKind> = resources.iter().map(|r| kind_of(r, since)).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ChangeKind::Created,
+                ChangeKind::Updated,
+                ChangeKind::Deleted
+            ]
+        );
+
+        let role: Resource = serde_json::from_str(
+            r#"{"id": "4", "type": "roles", "attributes": {
+                "created_at": "2024-02-01T10:00:00+01:00", "end_on": "2024-03-05"}}"#,
+        )
+        .unwrap();
+        let day = |day: u32| since + chrono::Duration::days(day.into());
+        assert_eq!(role_kind_of(&role, since, day(3)), ChangeKind::Updated);
+        assert_eq!(role_kind_of(&role, since, day(4)), ChangeKind::Deleted);
+        assert_eq!(
+            removed_ids(
+                &["1".to_string(), "2".to_string()],
+                &["2".to_string(), "3".to_string()]
+            ),
+            vec!["1".to_string()]
+        );
+    }
+
+    #[test]
+    fn cursor() {
+        let path = std::env::temp_dir().join("midata_sync_cursor_test.json");
+        let since = DateTime::parse_from_rfc3339("2024-03-01T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let mut cursor = SyncCursor::new(SyncScope::Layer(42), since);
+        cursor.write(&path).unwrap();
+        assert_eq!(SyncCursor::read(&path).unwrap(), cursor);
+
+        let changes = Changes {
+            people: vec![],
+            groups: vec![],
+            roles: vec![],
+            deleted_people: vec![],
+            deleted_groups: vec![],
+            people_ids: vec!["10".to_string()],
+            group_ids: vec!["42".to_string()],
+            until: since + chrono::Duration::days(1),
+        };
+        assert!(changes.is_empty());
+        cursor.advance(&changes);
+        assert_eq!(cursor.synced_until, changes.until);
+        assert_eq!(cursor.people_ids, changes.people_ids);
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,289 @@
+use super::api::ApiQuery;
+use super::{Error, MidataConnection, Person, Role};
+use chrono::NaiveDate;
+
+/// Time a person had a role
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct RolePeriod {
+    pub role_id: String,
+    /// type of the role, e.g. `Group::Pfadi::Einheitsleitung`
+    pub role_type: String,
+    pub label: Option<String>,
+    /// first day of the role
+    pub start: NaiveDate,
+    /// last day of the role. None if the role did not end.
+    pub end: Option<NaiveDate>,
+    /// participant role, see Role::is_tn()
+    pub is_tn: bool,
+}
+
+impl RolePeriod {
+    /// Period of a role. None if the start of the role is unknown.
+    pub fn of(role: &Role) -> Option<RolePeriod> {
+        Some(RolePeriod {
+            role_id: role.id.clone(),
+            role_type: role.role_type.clone(),
+            label: role.label.clone(),
+            start: role.start()?,
+            end: role.end(),
+            is_tn: role.is_tn(),
+        })
+    }
+
+    /// true if the role started and did not end before the day
+    pub fn is_active_on(&self, on: NaiveDate) -> bool {
+        self.start <= on && self.end.is_none_or(|end| end >= on)
+    }
+}
+
+/// Roles of a person in a group
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct GroupMembership {
+    pub group_id: String,
+    /// roles in the group in chronological order
+    pub periods: Vec<RolePeriod>,
+}
+
+impl GroupMembership {
+    /// first day of the first role in the group
+    pub fn start(&self) -> Option<NaiveDate> {
+        self.periods.iter().map(|period| period.start).min()
+    }
+
+    /// days with at least one role in the group up to the given day
+    pub fn active_days(&self, on: NaiveDate) -> i64 {
+        active_days(self.periods.iter(), on)
+    }
+}
+
+/// Number of days covered by the periods up to the given day (inclusive). Overlapping periods
+/// are counted once.
+pub fn active_days<'a, I>(periods: I, on: NaiveDate) -> i64
+where
+    I: Iterator<Item = &'a RolePeriod>,
+{
+    let mut ranges: Vec<(NaiveDate, NaiveDate)> = periods
+        .filter(|period| period.start <= on)
+        .map(|period| (period.start, period.end.map_or(on, |end| end.min(on))))
+        .filter(|(start, end)| start <= end)
+        .collect();
+    ranges.sort();
+    let mut days = 0;
+    let mut covered_until: Option<NaiveDate> = None;
+    for (start, end) in ranges {
+        let start = match covered_until {
+            Some(covered) if covered >= end => continue,
+            Some(covered) if covered >= start => covered.succ_opt().unwrap_or(covered),
+            _ => start,
+        };
+        days += (end - start).num_days() + 1;
+        covered_until = Some(end);
+    }
+    days
+}
+
+impl Role {
+    /// First day of the role: start_on if given by midata, the day it was created otherwise.
+    pub fn start(&self) -> Option<NaiveDate> {
+        self.start_on
+            .or_else(|| self.created_at.map(|created_at| created_at.date_naive()))
+    }
+
+    /// Last day of the role: end_on if given by midata, the day it was deleted otherwise. None
+    /// if the role did not end.
+    pub fn end(&self) -> Option<NaiveDate> {
+        self.end_on
+            .or_else(|| self.deleted_at.map(|deleted_at| deleted_at.date_naive()))
+    }
+
+    /// true if the role started and did not end before the day. Roles without known start are
+    /// active until they end.
+    pub fn is_active_on(&self, on: NaiveDate) -> bool {
+        self.start().is_none_or(|start| start <= on) && self.end().is_none_or(|end| end >= on)
+    }
+}
+
+impl Person {
+    /// Roles of the person per group in chronological order, starting with the group the person
+    /// joined first.
+    ///
+    /// # Note
+    /// Only the roles loaded with the person are included, which are usually the current ones.
+    /// Load the ended roles with MidataConnection::load_role_history() first.
+    pub fn membership_timeline(&self) -> Vec<GroupMembership> {
+        let mut timeline: Vec<GroupMembership> = vec![];
+        for role in &self.roles {
+            let group_id = match &role.links {
+                Some(links) => &links.group,
+                None => continue,
+            };
+            let period = match RolePeriod::of(role) {
+                Some(period) => period,
+                None => continue,
+            };
+            match timeline.iter_mut().find(|m| &m.group_id == group_id) {
+                Some(membership) => {
+                    if !membership
+                        .periods
+                        .iter()
+                        .any(|p| p.role_id == period.role_id)
+                    {
+                        membership.periods.push(period);
+                    }
+                }
+                None => timeline.push(GroupMembership {
+                    group_id: group_id.clone(),
+                    periods: vec![period],
+                }),
+            }
+        }
+        for membership in &mut timeline {
+            membership
+                .periods
+                .sort_by_key(|period| (period.start, period.end.is_none(), period.end));
+        }
+        timeline.sort_by_key(|membership| membership.start());
+        timeline
+    }
+
+    /// Completed years the person was a leader (Dienstjahre) up to the given day.
+    ///
+    /// Days are counted with the rule of is_leader(): the person had a role that day, but no
+    /// participant role (Role::is_tn()). Days on which the person led several groups are counted
+    /// once.
+    pub fn service_years(&self, on: NaiveDate) -> u32 {
+        let timeline = self.membership_timeline();
+        let periods = || timeline.iter().flat_map(|membership| &membership.periods);
+        // days with any role minus days with a participant role
+        let days = active_days(periods(), on) - active_days(periods().filter(|p| p.is_tn), on);
+        (days as f64 / 365.25) as u32
+    }
+}
+
+impl MidataConnection {
+    /// Load all roles of the people, including ended roles, and add them to their roles.
+    ///
+    /// Ended roles do not count for Person::is_tn() and Person::is_leader(), is_leiter is
+    /// updated accordingly.
+    ///
+    /// # Note
+    /// The roles are loaded using the JSON:API. Ended roles are included as far as midata keeps
+    /// them and the token may read them: roles ended with end_on are kept, roles deleted shortly
+    /// after being created are removed by hitobito.
+    pub fn load_role_history(&self, people: &mut [Person]) -> Result<(), Error> {
+        let ids: Vec<String> = people.iter().map(|person| person.id.clone()).collect();
+        for chunk in ids.chunks(100) {
+            let query = ApiQuery::new().filter("person_id", &chunk.join(","));
+            for role in self.query_roles(&query)? {
+                let person = people
+                    .iter_mut()
+                    .find(|person| role.person_id.as_ref() == Some(&person.id));
+                if let Some(person) = person {
+                    if !person.roles.iter().any(|r| r.id == role.id) {
+                        person.roles.push(role);
+                    }
+                }
+            }
+        }
+        for person in people.iter_mut() {
+            person.is_leiter = person.is_leader();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(text: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(text, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn timeline() {
+        let person: Person = serde_json::from_str(
+            r#"{"id": "10", "first_name": "Anna", "company": false, "links": {},
+                "roles": [
+                    {"id": "1", "role_type": "Group::Woelfe::Wolf",
+                     "created_at": "2010-08-20T10:00:00+02:00", "updated_at": "",
+                     "deleted_at": "2014-08-19 18:00:00 +0200",
+                     "links": {"group": "2", "layer_group": "1"}},
+                    {"id": "2", "role_type": "Group::Woelfe::Mitleitung",
+                     "created_at": "2018-01-01T10:00:00+01:00", "updated_at": null,
+                     "start_on": "2017-08-20", "end_on": "2020-08-19",
+                     "links": {"group": "2", "layer_group": "1"}},
+                    {"id": "3", "role_type": "Group::Woelfe::Einheitsleitung",
+                     "created_at": "2019-08-20T10:00:00+02:00", "updated_at": "",
+                     "links": {"group": "2", "layer_group": "1"}},
+                    {"id": "4", "role_type": "Group::Abteilung::Coach",
+                     "created_at": "2016-03-01T10:00:00+01:00", "updated_at": "",
+                     "end_on": "2016-12-31",
+                     "links": {"group": "1", "layer_group": "1"}},
+                    {"id": "5", "role_type": "Group::Abteilung::Coach", "created_at": "",
+                     "updated_at": "", "links": {"group": "1", "layer_group": "1"}}
+                ]}"#,
+        )
+        .unwrap();
+        let timeline = person.membership_timeline();
+        let groups: Vec<(&str, Vec<&str>)> = timeline
+            .iter()
+            .map(|m| {
+                let roles = m.periods.iter().map(|p| p.role_id.as_str()).collect();
+                (m.group_id.as_str(), roles)
+            })
+            .collect();
+        assert_eq!(groups, vec![("2", vec!["1", "2", "3"]), ("1", vec!["4"])]);
+        assert_eq!(timeline[0].periods[0].end, Some(date("2014-08-19")));
+        assert_eq!(timeline[0].start(), Some(date("2010-08-20")));
+
+        let on = date("2024-08-19");
+        assert!(person.roles[2].is_active_on(on));
+        assert!(!person.roles[1].is_active_on(on));
+        // 2016-03-01 to 2016-12-31 and 2017-08-20 to 2024-08-19
+        assert_eq!(person.service_years(on), 7);
+        assert_eq!(timeline[1].active_days(on), 306);
+
+        // leading while still being a Pio does not count as service
+        let mut pio = person.clone();
+        pio.roles[0].role_type = "Group::Pio::Pio".to_string();
+        pio.roles[0].deleted_at = None;
+        pio.roles[0].end_on = Some(date("2021-08-19"));
+        assert_eq!(pio.service_years(on), 3);
+        // the ended Pio role does not make the Einheitsleitung a participant
+        assert!(pio.is_leader());
+        assert!(!pio.is_tn());
+        // as long as the Pio role lasts, the person is a participant
+        pio.roles[0].end_on = None;
+        assert!(pio.is_tn());
+        assert!(!pio.is_leader());
+
+        let role: Role = serde_json::from_str(
+            r#"{"id": "6", "role_type": "Group::Woelfe::Wolf", "created_at": "gestern",
+                "updated_at": "", "start_on": "20.08.2010"}"#,
+        )
+        .unwrap();
+        assert_eq!((role.created_at, role.start_on), (None, None));
+    }
+
+    #[test]
+    fn overlapping_periods() {
+        let period = |start: &str, end: Option<&str>| RolePeriod {
+            role_id: String::new(),
+            role_type: String::new(),
+            label: None,
+            start: date(start),
+            end: end.map(date),
+            is_tn: false,
+        };
+        let periods = [
+            period("2020-01-01", Some("2020-01-10")),
+            period("2020-01-05", Some("2020-01-08")),
+            period("2020-01-09", Some("2020-01-20")),
+            period("2020-02-01", None),
+            period("2021-01-01", None),
+        ];
+        assert_eq!(active_days(periods.iter(), date("2020-02-05")), 20 + 5);
+        assert_eq!(active_days(periods.iter(), date("2019-12-31")), 0);
+    }
+}
@@ -0,0 +1,235 @@
+use super::{Error, MidataConnection, Person, Request};
+use chrono::NaiveDate;
+
+/// State of an invoice
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum InvoiceState {
+    Draft,
+    Issued,
+    Sent,
+    Payed,
+    Reminded,
+    Cancelled,
+    /// states not known to this crate
+    #[serde(other)]
+    Other,
+}
+
+/// Links of invoices to the recipient, items and payments
+#[derive(Deserialize, Debug, Clone)]
+struct InvoiceLinks {
+    recipient: Option<String>,
+    invoice_items: Option<Vec<String>>,
+    payments: Option<Vec<String>>,
+}
+
+/// Invoice (Rechnung) of a layer
+#[derive(Deserialize, Debug, Clone)]
+pub struct Invoice {
+    pub id: String,
+    pub title: String,
+    /// number of the invoice within the layer, e.g. `376803-3`
+    pub sequence_number: Option<String>,
+    pub state: InvoiceState,
+    /// QR or ESR reference used for the payment
+    pub reference: Option<String>,
+    pub esr_number: Option<String>,
+    pub description: Option<String>,
+    pub recipient_email: Option<String>,
+    pub recipient_address: Option<String>,
+    pub issued_at: Option<NaiveDate>,
+    pub sent_at: Option<NaiveDate>,
+    pub due_at: Option<NaiveDate>,
+    #[serde(default, deserialize_with = "deserialize_amount")]
+    pub total: f64,
+    #[serde(default, deserialize_with = "deserialize_amount")]
+    pub amount_paid: f64,
+    #[serde(default, deserialize_with = "deserialize_amount")]
+    pub amount_open: f64,
+    /// links to the recipient, items and payments
+    links: Option<InvoiceLinks>,
+
+    /// not mapped. items of the invoice
+    #[serde(skip)]
+    pub items: Vec<InvoiceItem>,
+    /// not mapped. payments received for the invoice
+    #[serde(skip)]
+    pub payments: Vec<Payment>,
+    /// not mapped. id of the layer the invoice was loaded from
+    #[serde(skip)]
+    group: u16,
+}
+
+/// Position of an invoice
+#[derive(Deserialize, Debug, Clone)]
+pub struct InvoiceItem {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_amount")]
+    pub unit_cost: f64,
+    pub count: u32,
+    #[serde(default, deserialize_with = "deserialize_amount")]
+    pub vat_rate: f64,
+    pub cost_center: Option<String>,
+    pub account: Option<String>,
+}
+
+/// Payment received for an invoice
+#[derive(Deserialize, Debug, Clone)]
+pub struct Payment {
+    pub id: String,
+    #[serde(deserialize_with = "deserialize_amount")]
+    pub amount: f64,
+    pub received_at: NaiveDate,
+    pub reference: Option<String>,
+}
+
+/// midata serializes amounts as strings (e.g. "12.50"), older versions as numbers.
+fn deserialize_amount<'de, D>(deserializer: D) -> Result<f64, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Amount {
+        Number(f64),
+        Text(String),
+    }
+    match serde::Deserialize::deserialize(deserializer)? {
+        Amount::Number(amount) => Ok(amount),
+        Amount::Text(amount) => amount.parse().map_err(serde::de::Error::custom),
+    }
+}
+
+/// Filters for loading invoices. All invoices are loaded with the default query.
+#[derive(Clone, Debug, Default)]
+pub struct InvoiceQuery {
+    /// only invoices in one of these states. Any state if empty.
+    pub states: Vec<InvoiceState>,
+    /// only invoices due on or after this date
+    pub due_from: Option<NaiveDate>,
+    /// only invoices due on or before this date
+    pub due_to: Option<NaiveDate>,
+    /// only invoices of this person
+    pub recipient: Option<u32>,
+}
+
+impl InvoiceQuery {
+    fn matches(&self, invoice: &Invoice) -> bool {
+        let due_at = invoice.due_at;
+        (self.states.is_empty() || self.states.contains(&invoice.state))
+            && self
+                .due_from
+                .is_none_or(|from| due_at.is_some_and(|due| due >= from))
+            && self
+                .due_to
+                .is_none_or(|to| due_at.is_some_and(|due| due <= to))
+            && self
+                .recipient
+                .is_none_or(|recipient| invoice.recipient_id() == Some(recipient))
+    }
+}
+
+impl Invoice {
+    /// id of the person the invoice is addressed to. None if sent to an address only.
+    pub fn recipient_id(&self) -> Option<u32> {
+        self.links.as_ref()?.recipient.as_ref()?.parse().ok()
+    }
+
+    /// Check if the invoice was issued and is not fully payed yet.
+    pub fn is_open(&self) -> bool {
+        matches!(
+            self.state,
+            InvoiceState::Issued | InvoiceState::Sent | InvoiceState::Reminded
+        )
+    }
+}
+
+/// Attach the open invoices to the people they are addressed to.
+///
+/// # Note
+/// Previously attached invoices are replaced.
+pub fn attach_open_invoices(persons: &mut [Person], invoices: &[Invoice]) {
+    for person in persons {
+        let id = person.id.parse().ok();
+        person.open_invoices = invoices
+            .iter()
+            .filter(|invoice| invoice.is_open() && invoice.recipient_id() == id)
+            .cloned()
+            .collect();
+    }
+}
+
+impl MidataConnection {
+    /// Load the invoices of a layer
+    ///
+    /// # Arguments
+    /// id: id of the layer group
+    /// query: filters to apply
+    pub fn load_invoices(&self, id: u16, query: &InvoiceQuery) -> Vec<Invoice> {
+        let mut invoices: Vec<Invoice> = vec![];
+        for r in self.load(vec![Request::Invoices(id)]) {
+            let (items, payments) = match r.linked {
+                Some(linked) => (
+                    linked.invoice_items.unwrap_or_default(),
+                    linked.payments.unwrap_or_default(),
+                ),
+                None => (vec![], vec![]),
+            };
+            for mut invoice in r.invoices.unwrap_or_default() {
+                invoice.group = id;
+                if let Some(links) = &invoice.links {
+                    let item_ids = links.invoice_items.clone().unwrap_or_default();
+                    let payment_ids = links.payments.clone().unwrap_or_default();
+                    invoice.items = items
+                        .iter()
+                        .filter(|item| item_ids.contains(&item.id))
+                        .cloned()
+                        .collect();
+                    invoice.payments = payments
+                        .iter()
+                        .filter(|payment| payment_ids.contains(&payment.id))
+                        .cloned()
+                        .collect();
+                }
+                if query.matches(&invoice) {
+                    invoices.push(invoice);
+                }
+            }
+        }
+        invoices
+    }
+
+    /// Record a payment received for an invoice
+    ///
+    /// # Arguments
+    /// invoice: invoice loaded with load_invoices()
+    /// amount: amount received
+    /// received_at: date the payment was received
+    /// reference: reference of the payment, e.g. the reference of the bank
+    pub fn create_payment(
+        &self,
+        invoice: &Invoice,
+        amount: f64,
+        received_at: NaiveDate,
+        reference: Option<&str>,
+    ) -> Result<(), Error> {
+        let mut params = vec![
+            ("payment[amount]".to_string(), format!("{:.2}", amount)),
+            ("payment[received_at]".to_string(), received_at.to_string()),
+        ];
+        if let Some(reference) = reference {
+            params.push(("payment[reference]".to_string(), reference.to_string()));
+        }
+        self.send(
+            reqwest::Method::POST,
+            &format!(
+                "https://db.scout.ch/de/groups/{}/invoices/{}/payments",
+                invoice.group, invoice.id
+            ),
+            params,
+        )
+    }
+}
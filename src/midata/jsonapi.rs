@@ -0,0 +1,255 @@
+//! Plumbing for the JSON:API of hitobito (`/api/...`)
+
+use super::{check_status, Error, FieldError, MidataConnection, LOAD_PAGE};
+use cached::Cached;
+use serde_json::{Map, Value};
+
+const API_URL: &str = "https://db.scout.ch/api";
+const MEDIA_TYPE: &str = "application/vnd.api+json";
+
+/// resource object of a JSON:API document
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct Resource {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) id: Option<String>,
+    #[serde(rename = "type")]
+    pub(crate) resource_type: String,
+    #[serde(default)]
+    pub(crate) attributes: Map<String, Value>,
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub(crate) relationships: Map<String, Value>,
+}
+
+/// JSON:API document with a single resource
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct Document {
+    pub(crate) data: Resource,
+}
+
+#[derive(Deserialize, Debug)]
+struct Links {
+    next: Option<String>,
+}
+
+/// JSON:API document with a list of resources, as responded when listing resources
+#[derive(Deserialize, Debug)]
+struct CollectionDocument {
+    data: Vec<Resource>,
+    /// related resources requested with `include`
+    #[serde(default)]
+    included: Vec<Resource>,
+    links: Option<Links>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ErrorSource {
+    pointer: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ErrorMeta {
+    attribute: Option<String>,
+    message: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ErrorObject {
+    title: Option<String>,
+    detail: Option<String>,
+    source: Option<ErrorSource>,
+    meta: Option<ErrorMeta>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ErrorDocument {
+    errors: Vec<ErrorObject>,
+}
+
+impl From<ErrorObject> for FieldError {
+    fn from(error: ErrorObject) -> FieldError {
+        let ErrorObject {
+            title,
+            detail,
+            source,
+            meta,
+        } = error;
+        let (attribute, message) = match meta {
+            Some(meta) => (meta.attribute, meta.message),
+            None => (None, None),
+        };
+        let field = attribute
+            .or_else(|| {
+                let pointer = source?.pointer?;
+                pointer.rsplit('/').next().map(str::to_string)
+            })
+            .unwrap_or_default();
+        let message = message.or(detail).or(title).unwrap_or_default();
+        FieldError { field, message }
+    }
+}
+
+impl Resource {
+    pub(crate) fn new(resource_type: &str, attributes: Map<String, Value>) -> Resource {
+        Resource {
+            id: None,
+            resource_type: resource_type.to_string(),
+            attributes,
+            relationships: Map::new(),
+        }
+    }
+
+    /// ids of the resources related by the relationship with the given name
+    pub(crate) fn related_ids(&self, name: &str) -> Vec<String> {
+        let identifiers = match self.relationships.get(name).and_then(|r| r.get("data")) {
+            Some(Value::Array(identifiers)) => identifiers.iter().collect(),
+            Some(identifier @ Value::Object(_)) => vec![identifier],
+            _ => vec![],
+        };
+        identifiers
+            .into_iter()
+            .filter_map(|identifier| identifier.get("id"))
+            .filter_map(|id| match id {
+                Value::String(id) => Some(id.clone()),
+                Value::Number(id) => Some(id.to_string()),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+impl MidataConnection {
+    /// Load resources from the JSON:API.
+    ///
+    /// # Arguments
+    /// path: path of the resources below /api, e.g. `people`
+    /// params: query parameters, e.g. `include=roles`
+    /// all_pages: follow the `next` links of paginated responses
+    ///
+    /// # Returns
+    /// the resources and the included related resources
+    #[tokio::main]
+    pub(crate) async fn load_resources(
+        &self,
+        path: &str,
+        params: &[(String, String)],
+        all_pages: bool,
+    ) -> Result<(Vec<Resource>, Vec<Resource>), Error> {
+        let client = reqwest::Client::new();
+        let mut headers = self.headers()?;
+        headers.insert(
+            "Accept",
+            reqwest::header::HeaderValue::from_static(MEDIA_TYPE),
+        );
+
+        let mut url = reqwest::Url::parse_with_params(&format!("{}/{}", API_URL, path), params)
+            .expect("Failed to parse url");
+        let mut data: Vec<Resource> = vec![];
+        let mut included: Vec<Resource> = vec![];
+        loop {
+            let response = client.get(url).headers(headers.clone()).send().await?;
+            check_status(response.status())?;
+            let mut document = response.json::<CollectionDocument>().await?;
+            data.append(&mut document.data);
+            for resource in document.included {
+                if !included
+                    .iter()
+                    .any(|r| r.id == resource.id && r.resource_type == resource.resource_type)
+                {
+                    included.push(resource);
+                }
+            }
+
+            match document.links.and_then(|links| links.next) {
+                Some(next) if all_pages => {
+                    url = reqwest::Url::parse(&next).map_err(|_| Error::UnexpectedResponse)?
+                }
+                _ => break,
+            }
+        }
+        Ok((data, included))
+    }
+
+    /// Send a document to the JSON:API and return the document responded.
+    ///
+    /// # Arguments
+    /// method: POST to create, PATCH to update or DELETE to destroy a resource
+    /// path: path of the resource below /api, e.g. `people/42`
+    /// document: document to send
+    ///
+    /// # Note
+    /// All cached responses are dropped afterwards since they might be outdated.
+    #[tokio::main]
+    pub(crate) async fn send_document(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        document: Option<&Document>,
+    ) -> Result<Option<Document>, Error> {
+        let url =
+            reqwest::Url::parse(&format!("{}/{}", API_URL, path)).expect("Failed to parse url");
+        let mut headers = self.headers()?;
+        headers.insert(
+            "Accept",
+            reqwest::header::HeaderValue::from_static(MEDIA_TYPE),
+        );
+        headers.insert(
+            "Content-Type",
+            reqwest::header::HeaderValue::from_static(MEDIA_TYPE),
+        );
+        let mut request = reqwest::Client::new().request(method, url).headers(headers);
+        if let Some(document) = document {
+            request = request.body(serde_json::to_vec(document).expect("Failed to serialize"));
+        }
+        let response = request.send().await?;
+        LOAD_PAGE.lock().await.cache_clear();
+
+        let status = response.status();
+        if status == reqwest::StatusCode::UNPROCESSABLE_ENTITY {
+            let errors = response.json::<ErrorDocument>().await?;
+            return Err(Error::Validation(
+                errors.errors.into_iter().map(FieldError::from).collect(),
+            ));
+        }
+        check_status(status)?;
+        if status == reqwest::StatusCode::NO_CONTENT {
+            return Ok(None);
+        }
+        let body = response.bytes().await?;
+        if body.is_empty() {
+            return Ok(None);
+        }
+        serde_json::from_slice(&body)
+            .map(Some)
+            .map_err(|_| Error::UnexpectedResponse)
+    }
+}
+
+/// numeric id of a resource as expected in attributes like `person_id`
+pub(crate) fn numeric_id(id: &str) -> Result<u32, Error> {
+    id.parse().map_err(|_| Error::UnexpectedResponse)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn field_errors() {
+        let errors: ErrorDocument = serde_json::from_str(
+            r#"{"errors": [
+                {"code": "unprocessable_entity", "status": "422", "title": "Validation Error",
+                 "detail": "Haupt-E-Mail ist nicht gültig",
+                 "source": {"pointer": "/data/attributes/email"},
+                 "meta": {"attribute": "email", "message": "ist nicht gültig"}},
+                {"title": "Validation Error", "detail": "Geburtstag ist kein Datum",
+                 "source": {"pointer": "/data/attributes/birthday"}}
+            ]}"#,
+        )
+        .unwrap();
+        let errors: Vec<FieldError> = errors.errors.into_iter().map(FieldError::from).collect();
+        assert_eq!(errors[0].field, "email");
+        assert_eq!(errors[0].message, "ist nicht gültig");
+        assert_eq!(errors[1].field, "birthday");
+        assert_eq!(errors[1].message, "Geburtstag ist kein Datum");
+    }
+}
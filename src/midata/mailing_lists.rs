@@ -0,0 +1,308 @@
+use super::jsonapi::numeric_id;
+use super::{link_roles, Error, MidataConnection, Person, Request};
+
+/// Who may subscribe to a mailing list
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SubscribableFor {
+    Nobody,
+    /// only people matching the configured subscriptions
+    Configured,
+    Anyone,
+}
+
+/// How people matching the subscriptions of a mailing list receive it
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SubscribableMode {
+    /// people have to subscribe themselves
+    OptIn,
+    /// people receive the mailing list until they unsubscribe
+    OptOut,
+}
+
+/// Links of mailing lists to their subscriptions
+#[derive(Deserialize, Debug, Clone)]
+struct MailingListLinks {
+    subscriptions: Option<Vec<String>>,
+}
+
+/// Mailing list (Abo) of a group
+#[derive(Deserialize, Debug, Clone)]
+pub struct MailingList {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub publisher: Option<String>,
+    /// local part of the email address of the list
+    pub mail_name: Option<String>,
+    pub subscribable_for: Option<SubscribableFor>,
+    pub subscribable_mode: Option<SubscribableMode>,
+    /// links to the subscriptions, only populated when loading a single list
+    links: Option<MailingListLinks>,
+
+    /// not mapped. id of the group the mailing list belongs to
+    #[serde(skip)]
+    group: u16,
+}
+
+/// Subscription to a mailing list as loaded from midata
+#[derive(Deserialize, Debug, Clone)]
+pub struct Subscription {
+    pub id: String,
+    /// Person, Group, Event or PeopleFilter
+    subscriber_type: String,
+    subscriber_id: String,
+    /// the subscriber is excluded from the list instead of subscribed to it
+    #[serde(default)]
+    pub excluded: bool,
+    /// role types of a group subscription
+    #[serde(default)]
+    role_types: Vec<String>,
+}
+
+/// Subscriber of a subscription
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Subscriber {
+    Person(u32),
+    /// people with one of the role types in the group or any group below it
+    Group {
+        id: u16,
+        role_types: Vec<String>,
+    },
+    /// participants of an event
+    Event(u32),
+    /// people matching a saved people filter
+    Filter(u32),
+    /// subscriber types not known to this crate
+    Other(String, String),
+}
+
+impl Subscription {
+    pub fn subscriber(&self) -> Subscriber {
+        let id = &self.subscriber_id;
+        match (self.subscriber_type.as_str(), id.parse::<u32>()) {
+            ("Person", Ok(id)) => Subscriber::Person(id),
+            ("Group", Ok(id)) if id <= u16::MAX as u32 => Subscriber::Group {
+                id: id as u16,
+                role_types: self.role_types.clone(),
+            },
+            ("Event", Ok(id)) => Subscriber::Event(id),
+            ("PeopleFilter", Ok(id)) => Subscriber::Filter(id),
+            _ => Subscriber::Other(self.subscriber_type.clone(), id.clone()),
+        }
+    }
+}
+
+impl MailingList {
+    /// id of the group the mailing list belongs to
+    pub fn group_id(&self) -> u16 {
+        self.group
+    }
+
+    fn ids(&self) -> Result<(u16, u32), Error> {
+        let id = self.id.parse().map_err(|_| Error::UnexpectedResponse)?;
+        Ok((self.group, id))
+    }
+
+    fn subscriptions_url(&self) -> String {
+        format!(
+            "https://db.scout.ch/de/groups/{}/mailing_lists/{}/subscriptions",
+            self.group, self.id
+        )
+    }
+}
+
+impl MidataConnection {
+    /// Load the mailing lists of a group
+    ///
+    /// # Arguments
+    /// id: id of the group
+    pub fn load_mailing_lists(&self, id: u16) -> Vec<MailingList> {
+        let mut lists: Vec<MailingList> = vec![];
+        for r in self.load(vec![Request::MailingLists(id)]) {
+            for mut list in r.mailing_lists.unwrap_or_default() {
+                list.group = id;
+                lists.push(list);
+            }
+        }
+        lists
+    }
+
+    /// Load the subscriptions of a mailing list, including the exclusions.
+    pub fn load_subscriptions(&self, list: &MailingList) -> Result<Vec<Subscription>, Error> {
+        let (gid, id) = list.ids()?;
+        let mut subscriptions: Vec<Subscription> = vec![];
+        for r in self.try_load(vec![Request::MailingList(gid, id)])? {
+            let linked = r
+                .linked
+                .and_then(|linked| linked.subscriptions)
+                .unwrap_or_default();
+            for list in r.mailing_lists.unwrap_or_default() {
+                let ids = list
+                    .links
+                    .and_then(|links| links.subscriptions)
+                    .unwrap_or_default();
+                subscriptions.extend(
+                    linked
+                        .iter()
+                        .filter(|subscription| ids.contains(&subscription.id))
+                        .cloned(),
+                );
+            }
+        }
+        Ok(subscriptions)
+    }
+
+    /// Load the subscribers explicitly excluded from a mailing list.
+    pub fn load_exclusions(&self, list: &MailingList) -> Result<Vec<Subscription>, Error> {
+        Ok(self
+            .load_subscriptions(list)?
+            .into_iter()
+            .filter(|subscription| subscription.excluded)
+            .collect())
+    }
+
+    /// Load the people effectively receiving a mailing list, i.e. all people matching a
+    /// subscription without the excluded ones.
+    ///
+    /// # Note
+    /// The people are not fully loaded. Each person remembers the group of its first role so
+    /// that Person::load() can be used.
+    pub fn load_recipients(&self, list: &MailingList) -> Result<Vec<Person>, Error> {
+        let (gid, id) = list.ids()?;
+        let mut persons = link_roles(
+            self.try_load(vec![Request::MailingListRecipients(gid, id)])?,
+            false,
+        );
+        persons.iter_mut().for_each(Person::request_from_own_group);
+        Ok(persons)
+    }
+
+    /// Subscribe a person to an opt-in mailing list
+    ///
+    /// # Errors
+    /// Error::NotOptIn if people can not subscribe themselves to the list,
+    /// Error::Forbidden if the token may not modify the subscriptions of the person.
+    pub fn subscribe(&self, list: &MailingList, person: &Person) -> Result<(), Error> {
+        if list.subscribable_mode != Some(SubscribableMode::OptIn)
+            || list.subscribable_for == Some(SubscribableFor::Nobody)
+        {
+            return Err(Error::NotOptIn);
+        }
+        self.send(
+            reqwest::Method::POST,
+            &format!("{}/person", list.subscriptions_url()),
+            vec![("subscription[subscriber_id]".to_string(), person.id.clone())],
+        )
+    }
+
+    /// Unsubscribe a person from a mailing list
+    ///
+    /// # Note
+    /// A direct subscription of the person is removed. If the person receives the list through a
+    /// group, an event or because the list is opt-out, the person is excluded instead.
+    pub fn unsubscribe(&self, list: &MailingList, person: &Person) -> Result<(), Error> {
+        let subscriber = Subscriber::Person(numeric_id(&person.id)?);
+        let subscription = self
+            .load_subscriptions(list)?
+            .into_iter()
+            .find(|subscription| !subscription.excluded && subscription.subscriber() == subscriber);
+        match subscription {
+            Some(subscription) => self.remove_subscription(list, &subscription),
+            None => self.send(
+                reqwest::Method::POST,
+                &format!("{}/exclude_person", list.subscriptions_url()),
+                vec![("subscription[subscriber_id]".to_string(), person.id.clone())],
+            ),
+        }
+    }
+
+    /// Subscribe the people with one of the role types in a group (or any group below it)
+    ///
+    /// # Arguments
+    /// list: mailing list to subscribe to
+    /// group: id of the group to subscribe
+    /// role_types: role types to subscribe, e.g. `Group::Abteilung::Abteilungsleitung`
+    pub fn subscribe_group(
+        &self,
+        list: &MailingList,
+        group: u16,
+        role_types: &[String],
+    ) -> Result<(), Error> {
+        let mut params = vec![("subscription[subscriber_id]".to_string(), group.to_string())];
+        for role_type in role_types {
+            params.push((
+                "subscription[role_types][]".to_string(),
+                role_type.to_owned(),
+            ));
+        }
+        self.send(
+            reqwest::Method::POST,
+            &format!("{}/group", list.subscriptions_url()),
+            params,
+        )
+    }
+
+    /// Remove all subscriptions of a group from a mailing list
+    pub fn unsubscribe_group(&self, list: &MailingList, group: u16) -> Result<(), Error> {
+        for subscription in self.load_subscriptions(list)? {
+            if let Subscriber::Group { id, .. } = subscription.subscriber() {
+                if id == group {
+                    self.remove_subscription(list, &subscription)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove a subscription or exclusion from a mailing list
+    pub fn remove_subscription(
+        &self,
+        list: &MailingList,
+        subscription: &Subscription,
+    ) -> Result<(), Error> {
+        self.send(
+            reqwest::Method::DELETE,
+            &format!("{}/{}", list.subscriptions_url(), subscription.id),
+            vec![],
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subscriber() {
+        let subscriptions: Vec<Subscription> = serde_json::from_str(
+            r#"[
+                {"id": "1", "subscriber_type": "Person", "subscriber_id": "42"},
+                {"id": "2", "subscriber_type": "Group", "subscriber_id": "6497",
+                    "role_types": ["Group::Abteilung::Abteilungsleitung"]},
+                {"id": "3", "subscriber_type": "Event", "subscriber_id": "7", "excluded": true},
+                {"id": "4", "subscriber_type": "PeopleFilter", "subscriber_id": "3"},
+                {"id": "5", "subscriber_type": "Group", "subscriber_id": "70000"},
+                {"id": "6", "subscriber_type": "Person", "subscriber_id": "abc"}
+            ]"#,
+        )
+        .unwrap();
+        let subscribers: Vec<Subscriber> = subscriptions.iter().map(|s| s.subscriber()).collect();
+        assert_eq!(
+            subscribers,
+            vec![
+                Subscriber::Person(42),
+                Subscriber::Group {
+                    id: 6497,
+                    role_types: vec!["Group::Abteilung::Abteilungsleitung".to_string()],
+                },
+                Subscriber::Event(7),
+                Subscriber::Filter(3),
+                Subscriber::Other("Group".to_string(), "70000".to_string()),
+                Subscriber::Other("Person".to_string(), "abc".to_string()),
+            ]
+        );
+        assert!(subscriptions[2].excluded);
+    }
+}
@@ -0,0 +1,223 @@
+use super::api::ApiQuery;
+use super::jsonapi::{Document, Resource};
+use super::{Error, Group, MidataConnection, Person};
+use chrono::NaiveDate;
+use serde_json::{Map, Value};
+
+/// Person to create in midata, see MidataConnection::create_person()
+#[derive(Clone, Debug, Default)]
+pub struct NewPerson {
+    /// role the person gets in the group, e.g. `Group::Biber::Biber`
+    pub role_type: String,
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    /// scout's name
+    pub nickname: Option<String>,
+    pub email: Option<String>,
+    /// "m", "w" or None if unknown
+    pub gender: Option<String>,
+    pub birthday: Option<NaiveDate>,
+    pub address: Option<String>,
+    pub zip_code: Option<String>,
+    pub town: Option<String>,
+    pub country: Option<String>,
+}
+
+/// Changes to a person, see MidataConnection::update_person()
+///
+/// Only the fields which are set and differ from the value currently saved in midata are sent.
+/// Set a field to an empty string to clear it.
+#[derive(Clone, Debug, Default)]
+pub struct PersonPatch {
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub nickname: Option<String>,
+    pub company_name: Option<String>,
+    pub company: Option<bool>,
+    pub email: Option<String>,
+    pub gender: Option<String>,
+    pub birthday: Option<NaiveDate>,
+    pub address: Option<String>,
+    pub zip_code: Option<String>,
+    pub town: Option<String>,
+    pub country: Option<String>,
+}
+
+fn insert_text(attributes: &mut Map<String, Value>, name: &str, value: &Option<String>) {
+    if let Some(value) = value {
+        let value = if value.is_empty() {
+            Value::Null
+        } else {
+            Value::String(value.clone())
+        };
+        attributes.insert(name.to_string(), value);
+    }
+}
+
+/// insert a text attribute if it differs from the current value
+fn insert_changed(
+    attributes: &mut Map<String, Value>,
+    name: &str,
+    value: &Option<String>,
+    current: &Option<String>,
+) {
+    if let Some(value) = value {
+        if value.as_str() != current.as_deref().unwrap_or_default() {
+            insert_text(attributes, name, &Some(value.clone()));
+        }
+    }
+}
+
+impl NewPerson {
+    fn attributes(&self) -> Map<String, Value> {
+        let mut attributes = Map::new();
+        insert_text(&mut attributes, "first_name", &self.first_name);
+        insert_text(&mut attributes, "last_name", &self.last_name);
+        insert_text(&mut attributes, "nickname", &self.nickname);
+        insert_text(&mut attributes, "email", &self.email);
+        insert_text(&mut attributes, "gender", &self.gender);
+        insert_text(
+            &mut attributes,
+            "birthday",
+            &self.birthday.map(|birthday| birthday.to_string()),
+        );
+        insert_text(&mut attributes, "address", &self.address);
+        insert_text(&mut attributes, "zip_code", &self.zip_code);
+        insert_text(&mut attributes, "town", &self.town);
+        insert_text(&mut attributes, "country", &self.country);
+        attributes
+    }
+}
+
+impl PersonPatch {
+    fn attributes(&self, person: &Person) -> Map<String, Value> {
+        let mut attributes = Map::new();
+        insert_changed(
+            &mut attributes,
+            "first_name",
+            &self.first_name,
+            &person.first_name,
+        );
+        insert_changed(
+            &mut attributes,
+            "last_name",
+            &self.last_name,
+            &person.last_name,
+        );
+        insert_changed(
+            &mut attributes,
+            "nickname",
+            &self.nickname,
+            &person.nickname,
+        );
+        insert_changed(
+            &mut attributes,
+            "company_name",
+            &self.company_name,
+            &person.company_name,
+        );
+        if let Some(company) = self.company {
+            if company != person.company {
+                attributes.insert("company".to_string(), Value::Bool(company));
+            }
+        }
+        insert_changed(&mut attributes, "email", &self.email, &person.email);
+        insert_changed(&mut attributes, "gender", &self.gender, &person.gender);
+        if let Some(birthday) = self.birthday {
+            if Some(birthday) != person.birthday {
+                attributes.insert("birthday".to_string(), Value::String(birthday.to_string()));
+            }
+        }
+        insert_changed(&mut attributes, "address", &self.address, &person.address);
+        insert_changed(
+            &mut attributes,
+            "zip_code",
+            &self.zip_code,
+            &person.zip_code,
+        );
+        insert_changed(&mut attributes, "town", &self.town, &person.town);
+        insert_changed(&mut attributes, "country", &self.country, &person.country);
+        attributes
+    }
+}
+
+impl MidataConnection {
+    /// Create a person with a role in a group
+    ///
+    /// # Arguments
+    /// group: group the person gets a role in
+    /// person: attributes of the person and type of the role
+    ///
+    /// # Errors
+    /// Error::InvalidRoleType if the role type does not exist in groups of that type,
+    /// Error::Validation with the invalid fields if midata rejects the person,
+    /// Error::RoleNotCreated with the id of the created person if the role could not be created.
+    ///
+    /// # Note
+    /// The created person is loaded using the JSON:API, see query_people().
+    pub fn create_person(&self, group: &Group, person: NewPerson) -> Result<Person, Error> {
+        super::roles::check_role_type(group, &person.role_type)?;
+        let document = Document {
+            data: Resource::new("people", person.attributes()),
+        };
+        let created = self
+            .send_document(reqwest::Method::POST, "people", Some(&document))?
+            .and_then(|document| document.data.id)
+            .ok_or(Error::UnexpectedResponse)?;
+
+        self.create_role_of(&created, group, &person.role_type, None)
+            .map_err(|error| Error::RoleNotCreated {
+                person_id: created.clone(),
+                error: Box::new(error),
+            })?;
+
+        let mut person = self.reload_person(&created)?;
+        if let Ok(gid) = group.id.parse() {
+            person.requested_by_group = gid;
+        }
+        Ok(person)
+    }
+
+    /// Update the changed attributes of a person and reload the person afterwards.
+    ///
+    /// # Errors
+    /// Error::Validation with the invalid fields if midata rejects the changes. The person is
+    /// not modified in that case. Errors while reloading are returned as well, the changes are
+    /// saved in midata then.
+    ///
+    /// # Note
+    /// The person is reloaded using the JSON:API before the patch is compared to it, so fields
+    /// missing in a partly loaded person are not skipped. See query_people().
+    pub fn update_person(&self, person: &mut Person, patch: PersonPatch) -> Result<(), Error> {
+        let current = self.reload_person(&person.id)?;
+        let attributes = patch.attributes(&current);
+        let gid = person.requested_by_group;
+        *person = if attributes.is_empty() {
+            current
+        } else {
+            let mut resource = Resource::new("people", attributes);
+            resource.id = Some(person.id.clone());
+            self.send_document(
+                reqwest::Method::PATCH,
+                &format!("people/{}", person.id),
+                Some(&Document { data: resource }),
+            )?;
+            self.reload_person(&person.id)?
+        };
+        if gid != 0 {
+            person.requested_by_group = gid;
+        }
+        Ok(())
+    }
+
+    /// load a person with its roles and phone numbers using the JSON:API
+    fn reload_person(&self, id: &str) -> Result<Person, Error> {
+        let query = ApiQuery::new()
+            .filter("id", id)
+            .include("roles")
+            .include("phone_numbers");
+        self.query_people(&query)?
+            .pop()
+            .ok_or(Error::UnexpectedResponse)
+    }
+}
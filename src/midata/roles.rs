@@ -0,0 +1,135 @@
+use super::jsonapi::{numeric_id, Document, Resource};
+use super::{parse_timestamp, Error, Group, MidataConnection, Person, Role, RolesLinks};
+use chrono::NaiveDate;
+use serde_json::{Map, Value};
+
+impl MidataConnection {
+    /// Give a person a role in a group
+    ///
+    /// # Arguments
+    /// person: person to get the role
+    /// group: group the role belongs to
+    /// role_type: type of the role, e.g. `Group::Woelfe::Wolf`
+    /// start_on: first day of the role
+    ///
+    /// # Errors
+    /// Error::InvalidRoleType if the role type does not exist in groups of that type.
+    pub fn create_role(
+        &self,
+        person: &Person,
+        group: &Group,
+        role_type: &str,
+        start_on: NaiveDate,
+    ) -> Result<Role, Error> {
+        self.create_role_of(&person.id, group, role_type, Some(start_on))
+    }
+
+    /// End a role
+    ///
+    /// # Arguments
+    /// role: role to end
+    /// end_on: last day of the role
+    pub fn end_role(&self, role: &Role, end_on: NaiveDate) -> Result<(), Error> {
+        let mut attributes = Map::new();
+        attributes.insert("end_on".to_string(), Value::String(end_on.to_string()));
+        let mut resource = Resource::new("roles", attributes);
+        resource.id = Some(role.id.clone());
+        self.send_document(
+            reqwest::Method::PATCH,
+            &format!("roles/{}", role.id),
+            Some(&Document { data: resource }),
+        )?;
+        Ok(())
+    }
+
+    /// Move a person from one role to a role in another group, e.g. from the Wölfe to the Pfadi.
+    ///
+    /// # Arguments
+    /// person: person owning the role
+    /// role: role to end the day before `on`
+    /// group: group of the new role
+    /// role_type: type of the new role
+    /// on: first day of the new role
+    ///
+    /// # Note
+    /// The new role is created before the old one is ended, so the person is never left without
+    /// a role. If ending the old role fails, the person has both roles.
+    pub fn transfer_role(
+        &self,
+        person: &Person,
+        role: &Role,
+        group: &Group,
+        role_type: &str,
+        on: NaiveDate,
+    ) -> Result<Role, Error> {
+        let new_role = self.create_role(person, group, role_type, on)?;
+        self.end_role(role, on.pred_opt().unwrap_or(on))?;
+        Ok(new_role)
+    }
+
+    /// create a role for a person after checking the role type is allowed in the group
+    pub(crate) fn create_role_of(
+        &self,
+        person: &str,
+        group: &Group,
+        role_type: &str,
+        start_on: Option<NaiveDate>,
+    ) -> Result<Role, Error> {
+        check_role_type(group, role_type)?;
+
+        let mut attributes = Map::new();
+        attributes.insert("person_id".to_string(), Value::from(numeric_id(person)?));
+        attributes.insert("group_id".to_string(), Value::from(numeric_id(&group.id)?));
+        attributes.insert("type".to_string(), Value::String(role_type.to_string()));
+        if let Some(start_on) = start_on {
+            attributes.insert("start_on".to_string(), Value::String(start_on.to_string()));
+        }
+        let document = Document {
+            data: Resource::new("roles", attributes),
+        };
+        let created = self
+            .send_document(reqwest::Method::POST, "roles", Some(&document))?
+            .ok_or(Error::UnexpectedResponse)?
+            .data;
+
+        let timestamp = |name: &str| {
+            created
+                .attributes
+                .get(name)
+                .and_then(Value::as_str)
+                .and_then(parse_timestamp)
+        };
+        Ok(Role {
+            id: created.id.clone().ok_or(Error::UnexpectedResponse)?,
+            role_type: role_type.to_string(),
+            label: None,
+            created_at: timestamp("created_at"),
+            updated_at: timestamp("updated_at"),
+            deleted_at: None,
+            start_on,
+            end_on: None,
+            person_id: Some(person.to_string()),
+            links: Some(RolesLinks {
+                group: group.id.clone(),
+                layer_group: group
+                    .links
+                    .as_ref()
+                    .map(|links| links.layer_group.clone())
+                    .unwrap_or_else(|| group.id.clone()),
+            }),
+        })
+    }
+}
+
+/// Check the role type exists in groups of the type of the group.
+pub(crate) fn check_role_type(group: &Group, role_type: &str) -> Result<(), Error> {
+    let group_type = group.group_type();
+    if group_type.allows_role_type(role_type) {
+        Ok(())
+    } else {
+        Err(Error::InvalidRoleType {
+            role_type: role_type.to_string(),
+            group_type: group_type.class_name().to_string(),
+        })
+    }
+}
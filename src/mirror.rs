@@ -0,0 +1,646 @@
+//! Local SQLite mirror of the groups, people, roles, phone numbers and events of layers, e.g. to
+//! run reporting queries offline.
+//!
+//! # Schema
+//! ```sql
+//! groups (id INTEGER PRIMARY KEY, name TEXT NOT NULL, group_type TEXT NOT NULL,
+//!         parent_id INTEGER, layer_group_id INTEGER, address TEXT, zip_code INTEGER,
+//!         town TEXT, country TEXT)
+//! people (id INTEGER PRIMARY KEY, first_name TEXT, last_name TEXT, nickname TEXT,
+//!         company_name TEXT, company INTEGER NOT NULL, email TEXT, gender TEXT,
+//!         birthday TEXT, address TEXT, zip_code TEXT, town TEXT, country TEXT,
+//!         household_key TEXT, primary_group_id INTEGER)
+//! roles (id INTEGER PRIMARY KEY, person_id INTEGER NOT NULL, group_id INTEGER NOT NULL,
+//!        role_type TEXT NOT NULL, label TEXT, created_at TEXT, updated_at TEXT,
+//!        deleted_at TEXT, start_on TEXT, end_on TEXT)
+//! phone_numbers (id INTEGER PRIMARY KEY, person_id INTEGER NOT NULL, number TEXT NOT NULL,
+//!                label TEXT, public INTEGER)
+//! events (id INTEGER PRIMARY KEY, name TEXT NOT NULL, event_type TEXT, description TEXT,
+//!         location TEXT)
+//! event_groups (event_id INTEGER NOT NULL, group_id INTEGER NOT NULL)
+//! sync_state (layer_group_id INTEGER PRIMARY KEY, synced_until TEXT NOT NULL)
+//! ```
+//! Dates are stored as `YYYY-MM-DD`, timestamps as RFC 3339 in UTC, e.g.
+//! `2024-03-01T09:00:00+00:00`. Layers are loaded through
+//! the JSON:API, so their role types are class names, e.g. `Group::Pfadi::Leitpfadi`. People are
+//! kept as long as they have a role in a mirrored group.
+
+use crate::midata::{
+    ApiQuery, ChangeKind, Error, Event, Group, MidataConnection, Person, Role, SyncCursor,
+    SyncScope,
+};
+use chrono::{DateTime, Utc};
+use rusqlite::params;
+use std::collections::HashSet;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS groups (
+        id INTEGER PRIMARY KEY, name TEXT NOT NULL, group_type TEXT NOT NULL,
+        parent_id INTEGER, layer_group_id INTEGER, address TEXT, zip_code INTEGER,
+        town TEXT, country TEXT);
+    CREATE TABLE IF NOT EXISTS people (
+        id INTEGER PRIMARY KEY, first_name TEXT, last_name TEXT, nickname TEXT,
+        company_name TEXT, company INTEGER NOT NULL, email TEXT, gender TEXT,
+        birthday TEXT, address TEXT, zip_code TEXT, town TEXT, country TEXT,
+        household_key TEXT, primary_group_id INTEGER);
+    CREATE TABLE IF NOT EXISTS roles (
+        id INTEGER PRIMARY KEY, person_id INTEGER NOT NULL, group_id INTEGER NOT NULL,
+        role_type TEXT NOT NULL, label TEXT, created_at TEXT, updated_at TEXT,
+        deleted_at TEXT, start_on TEXT, end_on TEXT);
+    CREATE TABLE IF NOT EXISTS phone_numbers (
+        id INTEGER PRIMARY KEY, person_id INTEGER NOT NULL, number TEXT NOT NULL,
+        label TEXT, public INTEGER);
+    CREATE TABLE IF NOT EXISTS events (
+        id INTEGER PRIMARY KEY, name TEXT NOT NULL, event_type TEXT, description TEXT,
+        location TEXT);
+    CREATE TABLE IF NOT EXISTS event_groups (
+        event_id INTEGER NOT NULL, group_id INTEGER NOT NULL,
+        PRIMARY KEY (event_id, group_id));
+    CREATE TABLE IF NOT EXISTS sync_state (
+        layer_group_id INTEGER PRIMARY KEY, synced_until TEXT NOT NULL);
+    CREATE INDEX IF NOT EXISTS roles_person ON roles (person_id);
+    CREATE INDEX IF NOT EXISTS roles_group ON roles (group_id);
+    CREATE INDEX IF NOT EXISTS phone_numbers_person ON phone_numbers (person_id);
+";
+
+/// columns added to tables of mirrors created by earlier versions, as (table, column, type)
+const ADDED_COLUMNS: &[(&str, &str, &str)] =
+    &[("roles", "start_on", "TEXT"), ("roles", "end_on", "TEXT")];
+
+/// number of ids filtered for in one request
+const IDS_PER_REQUEST: usize = 100;
+
+/// Errors while mirroring
+#[derive(Debug)]
+pub enum MirrorError {
+    Sqlite(rusqlite::Error),
+    Midata(Error),
+}
+
+impl std::fmt::Display for MirrorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            MirrorError::Sqlite(e) => write!(f, "Could not access the mirror: {}", e),
+            MirrorError::Midata(e) => write!(f, "Could not load from midata: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for MirrorError {}
+
+impl From<rusqlite::Error> for MirrorError {
+    fn from(e: rusqlite::Error) -> Self {
+        MirrorError::Sqlite(e)
+    }
+}
+
+impl From<Error> for MirrorError {
+    fn from(e: Error) -> Self {
+        MirrorError::Midata(e)
+    }
+}
+
+/// ids of midata are numbers given as strings
+fn id(id: &str) -> Option<i64> {
+    id.parse().ok()
+}
+
+fn insert_groups(db: &rusqlite::Connection, groups: &[Group]) -> rusqlite::Result<()> {
+    let mut statement = db.prepare(
+        "INSERT OR REPLACE INTO groups (id, name, group_type, parent_id, layer_group_id, \
+         address, zip_code, town, country) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+    )?;
+    for group in groups {
+        statement.execute(params![
+            id(&group.id),
+            group.name,
+            group.group_type().class_name(),
+            group.parent_id().and_then(id),
+            group.layer_group_id().and_then(id),
+            group.address,
+            group.zip_code,
+            group.town,
+            group.country,
+        ])?;
+    }
+    Ok(())
+}
+
+fn insert_roles(db: &rusqlite::Connection, roles: &[Role]) -> rusqlite::Result<()> {
+    let mut statement = db.prepare(
+        "INSERT OR REPLACE INTO roles (id, person_id, group_id, role_type, label, created_at, \
+         updated_at, deleted_at, start_on, end_on) \
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+    )?;
+    for role in roles {
+        let person_id = role.person_id.as_deref().and_then(id);
+        let group_id = role.links.as_ref().and_then(|links| id(&links.group));
+        if let (Some(person_id), Some(group_id)) = (person_id, group_id) {
+            statement.execute(params![
+                id(&role.id),
+                person_id,
+                group_id,
+                role.role_type,
+                role.label,
+                role.created_at.map(|t| t.to_rfc3339()),
+                role.updated_at.map(|t| t.to_rfc3339()),
+                role.deleted_at.map(|t| t.to_rfc3339()),
+                role.start_on.map(|start_on| start_on.to_string()),
+                role.end_on.map(|end_on| end_on.to_string()),
+            ])?;
+        }
+    }
+    Ok(())
+}
+
+/// insert the people with their roles
+///
+/// # Arguments
+/// replace_phone_numbers: replace the phone numbers of the people. Otherwise they are only
+/// replaced for people with phone numbers.
+fn insert_people(
+    db: &rusqlite::Connection,
+    people: &[Person],
+    replace_phone_numbers: bool,
+) -> rusqlite::Result<()> {
+    let mut statement = db.prepare(
+        "INSERT OR REPLACE INTO people (id, first_name, last_name, nickname, company_name, \
+         company, email, gender, birthday, address, zip_code, town, country, household_key, \
+         primary_group_id) \
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
+    )?;
+    let mut delete_phone_numbers = db.prepare("DELETE FROM phone_numbers WHERE person_id = ?1")?;
+    let mut insert_phone_number = db.prepare(
+        "INSERT OR REPLACE INTO phone_numbers (id, person_id, number, label, public) \
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+    )?;
+    for person in people {
+        statement.execute(params![
+            id(&person.id),
+            person.first_name,
+            person.last_name,
+            person.nickname,
+            person.company_name,
+            person.company,
+            person.email,
+            person.gender,
+            person.birthday.map(|birthday| birthday.to_string()),
+            person.address,
+            person.zip_code,
+            person.town,
+            person.country,
+            person.household_key,
+            person.primary_group_id().and_then(id),
+        ])?;
+        if replace_phone_numbers || !person.phone_numbers.is_empty() {
+            delete_phone_numbers.execute(params![id(&person.id)])?;
+            for phone_number in &person.phone_numbers {
+                insert_phone_number.execute(params![
+                    id(&phone_number.id),
+                    id(&person.id),
+                    phone_number.number,
+                    phone_number.label,
+                    phone_number.public,
+                ])?;
+            }
+        }
+        let roles: Vec<Role> = person
+            .roles
+            .iter()
+            .map(|role| Role {
+                person_id: Some(person.id.clone()),
+                ..role.clone()
+            })
+            .collect();
+        insert_roles(db, &roles)?;
+    }
+    Ok(())
+}
+
+fn insert_events(db: &rusqlite::Connection, events: &[Event]) -> rusqlite::Result<()> {
+    let mut statement = db.prepare(
+        "INSERT OR REPLACE INTO events (id, name, event_type, description, location) \
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+    )?;
+    let mut delete_groups = db.prepare("DELETE FROM event_groups WHERE event_id = ?1")?;
+    let mut insert_group =
+        db.prepare("INSERT OR REPLACE INTO event_groups (event_id, group_id) VALUES (?1, ?2)")?;
+    for event in events {
+        statement.execute(params![
+            id(&event.id),
+            event.name,
+            event.event_type,
+            event.description,
+            event.location,
+        ])?;
+        delete_groups.execute(params![id(&event.id)])?;
+        for group_id in event.group_ids() {
+            insert_group.execute(params![id(&event.id), group_id])?;
+        }
+    }
+    Ok(())
+}
+
+/// delete the groups with their roles
+fn delete_groups(db: &rusqlite::Connection, ids: &[String]) -> rusqlite::Result<()> {
+    for group_id in ids.iter().filter_map(|group_id| id(group_id)) {
+        db.execute("DELETE FROM roles WHERE group_id = ?1", params![group_id])?;
+        db.execute(
+            "DELETE FROM event_groups WHERE group_id = ?1",
+            params![group_id],
+        )?;
+        db.execute("DELETE FROM groups WHERE id = ?1", params![group_id])?;
+    }
+    Ok(())
+}
+
+/// delete people without roles and events without groups
+fn delete_orphans(db: &rusqlite::Connection) -> rusqlite::Result<()> {
+    db.execute_batch(
+        "DELETE FROM people WHERE id NOT IN (SELECT person_id FROM roles);
+         DELETE FROM phone_numbers WHERE person_id NOT IN (SELECT id FROM people);
+         DELETE FROM events WHERE id NOT IN (SELECT event_id FROM event_groups);",
+    )
+}
+
+fn set_synced_until(
+    db: &rusqlite::Connection,
+    layer_id: u16,
+    synced_until: DateTime<Utc>,
+) -> rusqlite::Result<()> {
+    db.execute(
+        "INSERT OR REPLACE INTO sync_state (layer_group_id, synced_until) VALUES (?1, ?2)",
+        params![layer_id, synced_until.to_rfc3339()],
+    )?;
+    Ok(())
+}
+
+/// Load resources filtered by a list of ids, splitting the ids into several requests.
+fn load_chunked<T>(
+    ids: &[String],
+    load: impl Fn(&str) -> Result<Vec<T>, Error>,
+) -> Result<Vec<T>, Error> {
+    let mut resources: Vec<T> = vec![];
+    for chunk in ids.chunks(IDS_PER_REQUEST) {
+        resources.append(&mut load(&chunk.join(","))?);
+    }
+    Ok(resources)
+}
+
+/// SQLite database mirroring layers of midata
+pub struct Mirror {
+    db: rusqlite::Connection,
+}
+
+impl Mirror {
+    /// Open the mirror stored in the given file. The file and the tables are created if needed.
+    pub fn open<P: AsRef<std::path::Path>>(path: P) -> Result<Mirror, MirrorError> {
+        Mirror::with_database(rusqlite::Connection::open(path)?)
+    }
+
+    /// Open a mirror only kept in memory
+    pub fn open_in_memory() -> Result<Mirror, MirrorError> {
+        Mirror::with_database(rusqlite::Connection::open_in_memory()?)
+    }
+
+    fn with_database(db: rusqlite::Connection) -> Result<Mirror, MirrorError> {
+        db.execute_batch(SCHEMA)?;
+        for (table, column, column_type) in ADDED_COLUMNS {
+            let exists: bool = db.query_row(
+                &format!(
+                    "SELECT COUNT(*) > 0 FROM pragma_table_info('{}') WHERE name = ?1",
+                    table
+                ),
+                params![column],
+                |row| row.get(0),
+            )?;
+            if !exists {
+                db.execute_batch(&format!(
+                    "ALTER TABLE {} ADD COLUMN {} {}",
+                    table, column, column_type
+                ))?;
+            }
+        }
+        Ok(Mirror { db })
+    }
+
+    /// the database, e.g. to run reporting queries. See the module documentation for the schema.
+    pub fn database(&self) -> &rusqlite::Connection {
+        &self.db
+    }
+
+    /// Store or replace groups
+    pub fn store_groups(&mut self, groups: &[Group]) -> Result<(), MirrorError> {
+        let transaction = self.db.transaction()?;
+        insert_groups(&transaction, groups)?;
+        Ok(transaction.commit()?)
+    }
+
+    /// Store or replace people with their roles and phone numbers.
+    ///
+    /// # Note
+    /// The phone numbers stored for a person are only replaced if the person has phone numbers,
+    /// as people listed in a group are loaded without them. Roles without group are skipped.
+    pub fn store_people(&mut self, people: &[Person]) -> Result<(), MirrorError> {
+        let transaction = self.db.transaction()?;
+        insert_people(&transaction, people, false)?;
+        Ok(transaction.commit()?)
+    }
+
+    /// Store or replace roles. Roles without person or group are skipped.
+    pub fn store_roles(&mut self, roles: &[Role]) -> Result<(), MirrorError> {
+        let transaction = self.db.transaction()?;
+        insert_roles(&transaction, roles)?;
+        Ok(transaction.commit()?)
+    }
+
+    /// Store or replace events with the groups organizing them
+    pub fn store_events(&mut self, events: &[Event]) -> Result<(), MirrorError> {
+        let transaction = self.db.transaction()?;
+        insert_events(&transaction, events)?;
+        Ok(transaction.commit()?)
+    }
+
+    /// ids given by a query with the id of a layer as parameter
+    fn ids(&self, query: &str, layer_id: u16) -> Result<Vec<String>, MirrorError> {
+        let mut statement = self.db.prepare(query)?;
+        let ids = statement.query_map(params![layer_id], |row| row.get::<_, i64>(0))?;
+        Ok(ids
+            .map(|id| id.map(|id| id.to_string()))
+            .collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// time until which the layer was synced. None if the layer was never loaded.
+    pub fn synced_until(&self, layer_id: u16) -> Result<Option<DateTime<Utc>>, MirrorError> {
+        let synced_until: Option<String> = self
+            .db
+            .query_row(
+                "SELECT synced_until FROM sync_state WHERE layer_group_id = ?1",
+                params![layer_id],
+                |row| row.get(0),
+            )
+            .map(Some)
+            .or_else(|e| match e {
+                rusqlite::Error::QueryReturnedNoRows => Ok(None),
+                e => Err(e),
+            })?;
+        Ok(synced_until
+            .and_then(|text| DateTime::parse_from_rfc3339(&text).ok())
+            .map(|synced_until| synced_until.with_timezone(&Utc)))
+    }
+
+    /// Load all groups of a layer with their roles, people, phone numbers and events using the
+    /// JSON:API and replace what was mirrored of the layer before.
+    pub fn load_layer(
+        &mut self,
+        connection: &MidataConnection,
+        layer_id: u16,
+    ) -> Result<(), MirrorError> {
+        let until = Utc::now();
+        let groups = connection
+            .query_groups(&ApiQuery::new().filter("layer_group_id", &layer_id.to_string()))?;
+        let group_ids: Vec<String> = groups.iter().map(|group| group.id.clone()).collect();
+        let roles = load_chunked(&group_ids, |ids| {
+            connection.query_roles(&ApiQuery::new().filter("group_id", ids))
+        })?;
+        let mut person_ids: Vec<String> = roles
+            .iter()
+            .filter_map(|role| role.person_id.clone())
+            .collect();
+        person_ids.sort();
+        person_ids.dedup();
+        let people = load_chunked(&person_ids, |ids| {
+            connection.query_people(&ApiQuery::new().include("phone_numbers").filter("id", ids))
+        })?;
+        let events = load_chunked(&group_ids, |ids| {
+            connection.query_events(&ApiQuery::new().filter("group_id", ids))
+        })?;
+
+        let transaction = self.db.transaction()?;
+        let mirrored_ids: Vec<String> = {
+            let mut statement =
+                transaction.prepare("SELECT id FROM groups WHERE layer_group_id = ?1")?;
+            let ids = statement.query_map(params![layer_id], |row| row.get::<_, i64>(0))?;
+            ids.map(|id| id.map(|id| id.to_string()))
+                .collect::<rusqlite::Result<_>>()?
+        };
+        delete_groups(&transaction, &mirrored_ids)?;
+        insert_groups(&transaction, &groups)?;
+        insert_roles(&transaction, &roles)?;
+        insert_people(&transaction, &people, true)?;
+        insert_events(&transaction, &events)?;
+        delete_orphans(&transaction)?;
+        set_synced_until(&transaction, layer_id, until)?;
+        Ok(transaction.commit()?)
+    }
+
+    /// Apply the changes of a layer since the last sync, see MidataConnection::changes_since().
+    /// The whole layer is loaded if it was never loaded before. Events no longer organized by a
+    /// group of the layer are removed from it.
+    ///
+    /// # Note
+    /// Finding the removed events needs the ids of all events of the layer, so every sync scans
+    /// all events of the layer in addition to the changed ones.
+    pub fn update_layer(
+        &mut self,
+        connection: &MidataConnection,
+        layer_id: u16,
+    ) -> Result<(), MirrorError> {
+        let since = match self.synced_until(layer_id)? {
+            Some(since) => since,
+            None => return self.load_layer(connection, layer_id),
+        };
+        let mut cursor = SyncCursor::new(SyncScope::Layer(layer_id), since);
+        cursor.group_ids = self.ids("SELECT id FROM groups WHERE layer_group_id = ?1", layer_id)?;
+        cursor.people_ids = self.ids(
+            "SELECT DISTINCT person_id FROM roles WHERE group_id IN \
+             (SELECT id FROM groups WHERE layer_group_id = ?1)",
+            layer_id,
+        )?;
+        let changes = connection.changes_since(&cursor)?;
+        let group_ids = changes.group_ids.clone();
+        let since_param = since.to_rfc3339();
+        let events = load_chunked(&group_ids, |ids| {
+            connection.query_events(&ApiQuery::new().filter("group_id", ids).filter_with(
+                "updated_at",
+                "gte",
+                &since_param,
+            ))
+        })?;
+        let event_ids: HashSet<String> = load_chunked(&group_ids, |ids| {
+            connection.query_events(
+                &ApiQuery::new()
+                    .filter("group_id", ids)
+                    .fields("events", &["id"]),
+            )
+        })?
+        .into_iter()
+        .map(|event| event.id)
+        .collect();
+        let removed_events: Vec<String> = self
+            .ids(
+                "SELECT DISTINCT event_id FROM event_groups WHERE group_id IN \
+                 (SELECT id FROM groups WHERE layer_group_id = ?1)",
+                layer_id,
+            )?
+            .into_iter()
+            .filter(|event_id| !event_ids.contains(event_id))
+            .collect();
+
+        let transaction = self.db.transaction()?;
+        delete_groups(&transaction, &changes.deleted_groups)?;
+        for event_id in removed_events.iter().filter_map(|event_id| id(event_id)) {
+            transaction.execute(
+                "DELETE FROM event_groups WHERE event_id = ?1 AND group_id IN \
+                 (SELECT id FROM groups WHERE layer_group_id = ?2)",
+                params![event_id, layer_id],
+            )?;
+        }
+        for person_id in changes
+            .deleted_people
+            .iter()
+            .filter_map(|person_id| id(person_id))
+        {
+            transaction.execute(
+                "DELETE FROM roles WHERE person_id = ?1 AND group_id IN \
+                 (SELECT id FROM groups WHERE layer_group_id = ?2)",
+                params![person_id, layer_id],
+            )?;
+        }
+        for change in &changes.groups {
+            match change.kind {
+                ChangeKind::Deleted => {
+                    delete_groups(&transaction, std::slice::from_ref(&change.record.id))?
+                }
+                _ => insert_groups(&transaction, std::slice::from_ref(&change.record))?,
+            }
+        }
+        for change in &changes.roles {
+            match change.kind {
+                ChangeKind::Deleted => {
+                    transaction.execute(
+                        "DELETE FROM roles WHERE id = ?1",
+                        params![id(&change.record.id)],
+                    )?;
+                }
+                _ => insert_roles(&transaction, std::slice::from_ref(&change.record))?,
+            }
+        }
+        let people: Vec<Person> = changes
+            .people
+            .iter()
+            .map(|change| change.record.clone())
+            .collect();
+        insert_people(&transaction, &people, true)?;
+        insert_events(&transaction, &events)?;
+        delete_orphans(&transaction)?;
+        set_synced_until(&transaction, layer_id, changes.until)?;
+        Ok(transaction.commit()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn store_and_query() {
+        let groups: Vec<Group> = serde_json::from_str(
+            r#"[
+                {"id": "1", "group_type": "Abteilung", "name": "Pfadi Muster",
+                 "links": {"parent": "0", "layer_group": "1"}},
+                {"id": "2", "group_type": "Wölfe", "name": "Meute Akela",
+                 "links": {"parent": "1", "layer_group": "1"}}
+            ]"#,
+        )
+        .unwrap();
+        let people: Vec<Person> = serde_json::from_str(
+            r#"[
+                {"id": "10", "first_name": "Anna", "company": false, "birthday": "2013-05-01",
+                 "links": {"primary_group": "2"},
+                 "phone_numbers": [{"id": "5", "number": "079 123 45 67", "label": "Mobil"}],
+                 "roles": [{"id": "100", "role_type": "Wolf", "created_at": "",
+                            "updated_at": "", "links": {"group": "2", "layer_group": "1"}}]},
+                {"id": "11", "first_name": "Ben", "company": false, "links": {},
+                 "roles": [{"id": "101", "role_type": "Wolf", "created_at": "",
+                            "updated_at": "", "links": {"group": "2", "layer_group": "1"}}]}
+            ]"#,
+        )
+        .unwrap();
+        let events: Vec<Event> = serde_json::from_str(
+            r#"[{"id": "7", "name": "Pfingstlager", "links": {"groups": ["1"]}}]"#,
+        )
+        .unwrap();
+
+        let mut mirror = Mirror::open_in_memory().unwrap();
+        mirror.store_groups(&groups).unwrap();
+        mirror.store_people(&people).unwrap();
+        mirror.store_events(&events).unwrap();
+
+        let db = mirror.database();
+        let count: i64 = db
+            .query_row(
+                "SELECT COUNT(*) FROM people p JOIN roles r ON r.person_id = p.id \
+                 JOIN groups g ON g.id = r.group_id WHERE g.name = 'Meute Akela'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(count, 2);
+        let number: String = db
+            .query_row(
+                "SELECT number FROM phone_numbers WHERE person_id = 10",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(number, "079 123 45 67");
+        let birthday: String = db
+            .query_row("SELECT birthday FROM people WHERE id = 10", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(birthday, "2013-05-01");
+        let event_group: i64 = db
+            .query_row(
+                "SELECT group_id FROM event_groups WHERE event_id = 7",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(event_group, 1);
+        assert_eq!(mirror.synced_until(1).unwrap(), None);
+    }
+
+    #[test]
+    fn add_role_dates_to_old_mirrors() {
+        let db = rusqlite::Connection::open_in_memory().unwrap();
+        db.execute_batch(
+            "CREATE TABLE roles (
+                id INTEGER PRIMARY KEY, person_id INTEGER NOT NULL, group_id INTEGER NOT NULL,
+                role_type TEXT NOT NULL, label TEXT, created_at TEXT, updated_at TEXT,
+                deleted_at TEXT);",
+        )
+        .unwrap();
+        let mut mirror = Mirror::with_database(db).unwrap();
+        let mut roles: Vec<Role> = serde_json::from_str(
+            r#"[{"id": "100", "role_type": "Group::Woelfe::Wolf",
+                 "created_at": "2024-03-01T10:00:00+01:00", "updated_at": "",
+                 "start_on": "2024-03-01", "end_on": "2025-08-15",
+                 "links": {"group": "2", "layer_group": "1"}}]"#,
+        )
+        .unwrap();
+        roles[0].person_id = Some("10".to_string());
+        mirror.store_roles(&roles).unwrap();
+        let (created_at, end_on): (String, String) = mirror
+            .database()
+            .query_row(
+                "SELECT created_at, end_on FROM roles WHERE id = 100",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(created_at, "2024-03-01T09:00:00+00:00");
+        assert_eq!(end_on, "2025-08-15");
+    }
+}
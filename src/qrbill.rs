@@ -0,0 +1,807 @@
+//! Swiss QR-bills (payment part with receipt) for groups collecting money from people, e.g.
+//! camp fees from the parents of the participants.
+//!
+//! The bills follow the Swiss Implementation Guidelines for the QR-bill (version 2.0) with
+//! structured addresses. Texts are in German.
+
+use crate::drawing::{Canvas, Color, Pdf, Svg};
+use crate::midata::{Group, Person};
+
+/// Errors when creating QR-bills
+#[derive(Debug, Clone, PartialEq)]
+pub enum QrBillError {
+    /// the group has no bank account
+    MissingAccount,
+    /// the IBAN is invalid or not from Switzerland or Liechtenstein
+    InvalidIban(String),
+    /// the address of the group or person is incomplete
+    MissingAddress(String),
+    /// the reference is invalid
+    InvalidReference(String),
+    /// QR-IBANs require a QR reference and QR references require a QR-IBAN
+    ReferenceMismatch,
+    /// the amount is not between 0.01 and 999'999'999.99
+    InvalidAmount(f64),
+    /// the text of the field is longer than allowed
+    TooLong { field: String, max: usize },
+    /// the QR code could not be generated, e.g. because too much text is given
+    QrCode(String),
+}
+
+impl std::fmt::Display for QrBillError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            QrBillError::MissingAccount => write!(f, "The group has no bank account."),
+            QrBillError::InvalidIban(iban) => write!(f, "Invalid IBAN {}", iban),
+            QrBillError::MissingAddress(name) => write!(f, "Incomplete address of {}", name),
+            QrBillError::InvalidReference(r) => write!(f, "Invalid reference {}", r),
+            QrBillError::ReferenceMismatch => write!(
+                f,
+                "QR references have to be used together with a QR-IBAN and vice versa."
+            ),
+            QrBillError::InvalidAmount(amount) => write!(f, "Invalid amount {:.2}", amount),
+            QrBillError::TooLong { field, max } => {
+                write!(f, "{} is longer than {} characters", field, max)
+            }
+            QrBillError::QrCode(e) => write!(f, "Could not create QR code: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for QrBillError {}
+
+/// Structured postal address
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Address {
+    pub name: String,
+    pub street: Option<String>,
+    pub house_number: Option<String>,
+    pub zip_code: String,
+    pub town: String,
+    /// two-letter ISO country code
+    pub country: String,
+}
+
+/// split "Musterstrasse 12a" into street and house number
+fn split_street(address: &str) -> (Option<String>, Option<String>) {
+    let address = address.lines().next().unwrap_or_default().trim();
+    if address.is_empty() {
+        return (None, None);
+    }
+    match address.rsplit_once(' ') {
+        Some((street, number)) if number.starts_with(|c: char| c.is_ascii_digit()) => {
+            (Some(street.to_string()), Some(number.to_string()))
+        }
+        _ => (Some(address.to_string()), None),
+    }
+}
+
+/// midata stores the country as ISO code, empty for Switzerland. None if the country is not an
+/// ISO code.
+fn country_code(country: Option<&str>) -> Option<String> {
+    match country.map(str::trim) {
+        None | Some("") => Some("CH".to_string()),
+        Some(country) if country.len() == 2 && country.chars().all(|c| c.is_ascii_alphabetic()) => {
+            Some(country.to_uppercase())
+        }
+        Some(_) => None,
+    }
+}
+
+/// Check that the text is not longer than max characters
+fn check_length(field: &str, text: Option<&str>, max: usize) -> Result<(), QrBillError> {
+    match text {
+        Some(text) if text.chars().count() > max => Err(QrBillError::TooLong {
+            field: field.to_string(),
+            max,
+        }),
+        _ => Ok(()),
+    }
+}
+
+impl Address {
+    /// address of a group. None if the group has no complete address.
+    pub fn of_group(group: &Group) -> Option<Address> {
+        let (street, house_number) = split_street(group.address.as_deref()?);
+        Some(Address {
+            name: group.name.clone(),
+            street,
+            house_number,
+            zip_code: group.zip_code?.to_string(),
+            town: group.town.clone().filter(|town| !town.is_empty())?,
+            country: country_code(group.country.as_deref())?,
+        })
+    }
+
+    /// address of a person. None if the person has no complete address.
+    pub fn of_person(person: &Person) -> Option<Address> {
+        let name = if person.company {
+            person.company_name.clone()?
+        } else {
+            let names: Vec<&str> = [&person.first_name, &person.last_name]
+                .iter()
+                .filter_map(|name| name.as_deref())
+                .collect();
+            names.join(" ")
+        };
+        let (street, house_number) = split_street(person.address.as_deref()?);
+        Some(Address {
+            name,
+            street,
+            house_number,
+            zip_code: person.zip_code.clone().filter(|zip| !zip.is_empty())?,
+            town: person.town.clone().filter(|town| !town.is_empty())?,
+            country: country_code(person.country.as_deref())?,
+        })
+    }
+
+    /// Check the lengths of the fields. The prefix names the address in errors.
+    fn validate(&self, prefix: &str) -> Result<(), QrBillError> {
+        let field = |name: &str| format!("{} {}", prefix, name);
+        check_length(&field("name"), Some(&self.name), 70)?;
+        check_length(&field("street"), self.street.as_deref(), 70)?;
+        check_length(&field("house number"), self.house_number.as_deref(), 16)?;
+        check_length(&field("zip code"), Some(&self.zip_code), 16)?;
+        check_length(&field("town"), Some(&self.town), 35)
+    }
+
+    /// lines of the address as printed on a letter, starting with the name
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![self.name.clone()];
+        let street: Vec<&str> = [&self.street, &self.house_number]
+            .iter()
+            .filter_map(|part| part.as_deref())
+            .collect();
+        if !street.is_empty() {
+            lines.push(street.join(" "));
+        }
+        if self.country == "CH" {
+            lines.push(format!("{} {}", self.zip_code, self.town));
+        } else {
+            lines.push(format!("{}-{} {}", self.country, self.zip_code, self.town));
+        }
+        lines
+    }
+
+    fn payload(&self) -> String {
+        [
+            "S",
+            &self.name,
+            self.street.as_deref().unwrap_or_default(),
+            self.house_number.as_deref().unwrap_or_default(),
+            &self.zip_code,
+            &self.town,
+            &self.country,
+        ]
+        .join("\n")
+    }
+}
+
+/// Currency of a QR-bill
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Currency {
+    Chf,
+    Eur,
+}
+
+impl Currency {
+    fn code(&self) -> &'static str {
+        match self {
+            Currency::Chf => "CHF",
+            Currency::Eur => "EUR",
+        }
+    }
+}
+
+/// Payment reference of a QR-bill
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reference {
+    /// no reference, only possible with a regular IBAN
+    None,
+    /// 27 digit QR reference, requires a QR-IBAN
+    Qr(String),
+    /// ISO 11649 creditor reference (RF...), requires a regular IBAN
+    Creditor(String),
+}
+
+fn without_spaces(text: &str) -> String {
+    text.chars().filter(|c| !c.is_whitespace()).collect()
+}
+
+fn mod10_check_digit(digits: &str) -> Option<u32> {
+    const TABLE: [u32; 10] = [0, 9, 4, 6, 8, 2, 7, 1, 3, 5];
+    let mut carry = 0;
+    for c in digits.chars() {
+        carry = TABLE[((carry + c.to_digit(10)?) % 10) as usize];
+    }
+    Some((10 - carry) % 10)
+}
+
+/// remainder of the number formed by replacing letters with 10..35 divided by 97
+fn mod97(text: &str) -> Option<u32> {
+    let mut remainder = 0;
+    for c in text.chars() {
+        let value = c.to_digit(36)?;
+        remainder = if value < 10 {
+            (remainder * 10 + value) % 97
+        } else {
+            (remainder * 100 + value) % 97
+        };
+    }
+    Some(remainder)
+}
+
+/// Generate a QR reference from up to 26 digits by padding them with zeros and appending the
+/// check digit.
+pub fn qr_reference(number: &str) -> Result<String, QrBillError> {
+    let number = without_spaces(number);
+    if number.is_empty() || number.len() > 26 || !number.chars().all(|c| c.is_ascii_digit()) {
+        return Err(QrBillError::InvalidReference(number));
+    }
+    let number = format!("{:0>26}", number);
+    let check_digit = mod10_check_digit(&number).unwrap();
+    Ok(format!("{}{}", number, check_digit))
+}
+
+/// Check length and check digit of a QR reference. Spaces are ignored.
+pub fn is_valid_qr_reference(reference: &str) -> bool {
+    let reference = without_spaces(reference);
+    reference.len() == 27
+        && reference.chars().all(|c| c.is_ascii_digit())
+        && mod10_check_digit(&reference[..26]) == reference[26..].parse().ok()
+}
+
+/// Generate an ISO 11649 creditor reference (RF...) from up to 21 letters and digits.
+pub fn creditor_reference(reference: &str) -> Result<String, QrBillError> {
+    let reference = without_spaces(reference).to_uppercase();
+    if reference.is_empty()
+        || reference.len() > 21
+        || !reference.chars().all(|c| c.is_ascii_alphanumeric())
+    {
+        return Err(QrBillError::InvalidReference(reference));
+    }
+    let check_digits = 98 - mod97(&format!("{}RF00", reference)).unwrap();
+    Ok(format!("RF{:02}{}", check_digits, reference))
+}
+
+/// Check an ISO 11649 creditor reference. Spaces are ignored.
+pub fn is_valid_creditor_reference(reference: &str) -> bool {
+    let reference = without_spaces(reference).to_uppercase();
+    reference.len() >= 5
+        && reference.len() <= 25
+        && reference.starts_with("RF")
+        && reference.chars().all(|c| c.is_ascii_alphanumeric())
+        && mod97(&format!("{}{}", &reference[4..], &reference[..4])) == Some(1)
+}
+
+/// Check an IBAN from Switzerland or Liechtenstein. Spaces are ignored.
+pub fn is_valid_iban(iban: &str) -> bool {
+    let iban = without_spaces(iban).to_uppercase();
+    iban.len() == 21
+        && (iban.starts_with("CH") || iban.starts_with("LI"))
+        && iban.chars().all(|c| c.is_ascii_alphanumeric())
+        && mod97(&format!("{}{}", &iban[4..], &iban[..4])) == Some(1)
+}
+
+/// Check if an IBAN is a QR-IBAN (institution id between 30000 and 31999).
+pub fn is_qr_iban(iban: &str) -> bool {
+    let iban = without_spaces(iban);
+    is_valid_iban(&iban)
+        && iban[4..9]
+            .parse::<u32>()
+            .is_ok_and(|iid| (30000..=31999).contains(&iid))
+}
+
+/// split text into blocks of the given size, starting from the left or the right
+fn blocks(text: &str, size: usize, from_right: bool) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let first = if from_right {
+        match chars.len() % size {
+            0 => size,
+            rest => rest,
+        }
+    } else {
+        size
+    };
+    let mut output = String::new();
+    for (i, c) in chars.iter().enumerate() {
+        if i >= first && (i - first) % size == 0 {
+            output.push(' ');
+        }
+        output.push(*c);
+    }
+    output
+}
+
+/// format an amount with spaces as thousands separator, e.g. "1 234.50"
+fn format_amount(amount: f64) -> String {
+    let text = format!("{:.2}", amount);
+    let (integer, fraction) = text.split_at(text.len() - 3);
+    format!("{}{}", blocks(integer, 3, true), fraction)
+}
+
+/// break text into lines of at most the given number of characters
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines: Vec<String> = vec![];
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        if !line.is_empty() && line.chars().count() + word.chars().count() + 1 > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// QR-bill payment part
+#[derive(Debug, Clone, PartialEq)]
+pub struct QrBill {
+    /// IBAN or QR-IBAN of the creditor
+    pub account: String,
+    pub creditor: Address,
+    /// amount to pay, None to let the debtor fill it in
+    pub amount: Option<f64>,
+    pub currency: Currency,
+    /// None to let the debtor fill in the address
+    pub debtor: Option<Address>,
+    pub reference: Reference,
+    /// unstructured message shown to the debtor, at most 140 characters
+    pub message: Option<String>,
+}
+
+impl QrBill {
+    /// Create a bill from the bank account and address of a group to a person.
+    ///
+    /// # Note
+    /// The debtor address is left empty if the person has no complete address.
+    pub fn for_person(
+        group: &Group,
+        person: &Person,
+        amount: Option<f64>,
+        reference: Reference,
+        message: Option<&str>,
+    ) -> Result<QrBill, QrBillError> {
+        let bill = QrBill {
+            account: group
+                .bank_account
+                .clone()
+                .ok_or(QrBillError::MissingAccount)?,
+            creditor: Address::of_group(group)
+                .ok_or_else(|| QrBillError::MissingAddress(group.name.clone()))?,
+            amount,
+            currency: Currency::Chf,
+            debtor: Address::of_person(person),
+            reference,
+            message: message.map(str::to_string),
+        };
+        bill.validate()?;
+        Ok(bill)
+    }
+
+    /// Check account, reference, amount and the lengths of the texts of the bill
+    pub fn validate(&self) -> Result<(), QrBillError> {
+        if !is_valid_iban(&self.account) {
+            return Err(QrBillError::InvalidIban(self.account.clone()));
+        }
+        let qr_iban = is_qr_iban(&self.account);
+        match &self.reference {
+            Reference::Qr(reference) if !is_valid_qr_reference(reference) => {
+                return Err(QrBillError::InvalidReference(reference.clone()))
+            }
+            Reference::Creditor(reference) if !is_valid_creditor_reference(reference) => {
+                return Err(QrBillError::InvalidReference(reference.clone()))
+            }
+            Reference::Qr(_) if !qr_iban => return Err(QrBillError::ReferenceMismatch),
+            Reference::None | Reference::Creditor(_) if qr_iban => {
+                return Err(QrBillError::ReferenceMismatch)
+            }
+            _ => {}
+        }
+        if let Some(amount) = self.amount {
+            if !(0.01..=999_999_999.99).contains(&amount) {
+                return Err(QrBillError::InvalidAmount(amount));
+            }
+        }
+        self.creditor.validate("creditor")?;
+        if let Some(debtor) = &self.debtor {
+            debtor.validate("debtor")?;
+        }
+        check_length("message", self.message.as_deref(), 140)
+    }
+
+    /// Content of the QR code
+    pub fn payload(&self) -> Result<String, QrBillError> {
+        self.validate()?;
+        let (reference_type, reference) = match &self.reference {
+            Reference::None => ("NON", String::new()),
+            Reference::Qr(reference) => ("QRR", without_spaces(reference)),
+            Reference::Creditor(reference) => ("SCOR", without_spaces(reference).to_uppercase()),
+        };
+        let fields = [
+            "SPC".to_string(),
+            "0200".to_string(),
+            "1".to_string(),
+            without_spaces(&self.account).to_uppercase(),
+            self.creditor.payload(),
+            // ultimate creditor, reserved for future use
+            "\n\n\n\n\n\n".to_string(),
+            self.amount
+                .map(|amount| format!("{:.2}", amount))
+                .unwrap_or_default(),
+            self.currency.code().to_string(),
+            self.debtor
+                .as_ref()
+                .map(Address::payload)
+                .unwrap_or_else(|| "\n\n\n\n\n\n".to_string()),
+            reference_type.to_string(),
+            reference,
+            self.message.clone().unwrap_or_default(),
+            "EPD".to_string(),
+        ];
+        Ok(fields.join("\n"))
+    }
+
+    /// Render the payment part with receipt (210 x 105 mm) as SVG
+    pub fn to_svg(&self) -> Result<String, QrBillError> {
+        let mut svg = Svg::new(210.0, 105.0);
+        self.draw(&mut svg, 0.0)?;
+        Ok(svg.finish())
+    }
+
+    /// Render the payment part with receipt at the bottom of an A4 page as PDF
+    pub fn to_pdf(&self) -> Result<Vec<u8>, QrBillError> {
+        render_pdf(std::slice::from_ref(self))
+    }
+
+    fn draw(&self, canvas: &mut dyn Canvas, top: f64) -> Result<(), QrBillError> {
+        let payload = self.payload()?;
+        let code = qrcode::QrCode::with_error_correction_level(&payload, qrcode::EcLevel::M)
+            .map_err(|e| QrBillError::QrCode(e.to_string()))?;
+
+        // separation lines
+        canvas.line(0.0, top, 210.0, top, 0.2);
+        canvas.line(62.0, top, 62.0, top + 105.0, 0.2);
+
+        let account = blocks(&without_spaces(&self.account).to_uppercase(), 4, false);
+        let reference = match &self.reference {
+            Reference::None => None,
+            Reference::Qr(reference) => Some(blocks(&without_spaces(reference), 5, true)),
+            Reference::Creditor(reference) => {
+                Some(blocks(&without_spaces(reference).to_uppercase(), 4, false))
+            }
+        };
+        let mut creditor = vec![account];
+        creditor.append(&mut self.creditor.lines());
+        let debtor = self.debtor.as_ref().map(Address::lines);
+
+        // receipt
+        let mut y = top + 10.0;
+        canvas.text(5.0, y, 11.0, true, "Empfangsschein");
+        y += 7.0;
+        y = section(canvas, 5.0, y, 6.0, 8.0, "Konto / Zahlbar an", &creditor);
+        if let Some(reference) = &reference {
+            y = section(
+                canvas,
+                5.0,
+                y,
+                6.0,
+                8.0,
+                "Referenz",
+                std::slice::from_ref(reference),
+            );
+        }
+        match &debtor {
+            Some(debtor) => {
+                section(canvas, 5.0, y, 6.0, 8.0, "Zahlbar durch", debtor);
+            }
+            None => {
+                canvas.text(5.0, y, 6.0, true, "Zahlbar durch (Name/Adresse)");
+                blank_field(canvas, 5.0, y + 1.5, 52.0, 20.0);
+            }
+        }
+        amount_section(canvas, 5.0, 18.0, top + 70.0, 6.0, 8.0, self);
+        canvas.text(38.0, top + 86.0, 6.0, true, "Annahmestelle");
+
+        // payment part
+        canvas.text(67.0, top + 10.0, 11.0, true, "Zahlteil");
+        draw_qr_code(canvas, &code, 67.0, top + 17.0, 46.0);
+        amount_section(canvas, 67.0, 80.0, top + 70.0, 8.0, 10.0, self);
+
+        let mut y = top + 10.0;
+        y = section(canvas, 118.0, y, 8.0, 10.0, "Konto / Zahlbar an", &creditor);
+        if let Some(reference) = &reference {
+            y = section(
+                canvas,
+                118.0,
+                y,
+                8.0,
+                10.0,
+                "Referenz",
+                std::slice::from_ref(reference),
+            );
+        }
+        if let Some(message) = &self.message {
+            y = section(
+                canvas,
+                118.0,
+                y,
+                8.0,
+                10.0,
+                "Zusätzliche Informationen",
+                &wrap(message, 40),
+            );
+        }
+        match &debtor {
+            Some(debtor) => {
+                section(canvas, 118.0, y, 8.0, 10.0, "Zahlbar durch", debtor);
+            }
+            None => {
+                canvas.text(118.0, y, 8.0, true, "Zahlbar durch (Name/Adresse)");
+                blank_field(canvas, 118.0, y + 1.5, 65.0, 25.0);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// draw a heading with the lines below it and return the position of the next heading
+fn section(
+    canvas: &mut dyn Canvas,
+    x: f64,
+    y: f64,
+    heading_size: f64,
+    size: f64,
+    heading: &str,
+    lines: &[String],
+) -> f64 {
+    let line_height = size * 0.4;
+    canvas.text(x, y, heading_size, true, heading);
+    let mut y = y + line_height;
+    for line in lines {
+        canvas.text(x, y, size, false, line);
+        y += line_height;
+    }
+    y + line_height * 0.8
+}
+
+fn amount_section(
+    canvas: &mut dyn Canvas,
+    x_currency: f64,
+    x_amount: f64,
+    y: f64,
+    heading_size: f64,
+    size: f64,
+    bill: &QrBill,
+) {
+    canvas.text(x_currency, y, heading_size, true, "Währung");
+    canvas.text(x_amount, y, heading_size, true, "Betrag");
+    canvas.text(
+        x_currency,
+        y + size * 0.45,
+        size,
+        false,
+        bill.currency.code(),
+    );
+    match bill.amount {
+        Some(amount) => canvas.text(
+            x_amount,
+            y + size * 0.45,
+            size,
+            false,
+            &format_amount(amount),
+        ),
+        None => blank_field(canvas, x_amount, y + 1.5, 30.0, 10.0),
+    }
+}
+
+/// draw the corner marks of a field the debtor fills in by hand
+fn blank_field(canvas: &mut dyn Canvas, x: f64, y: f64, width: f64, height: f64) {
+    let mark = 3.0;
+    let (right, bottom) = (x + width, y + height);
+    for (cx, cy, dx, dy) in [
+        (x, y, mark, mark),
+        (right, y, -mark, mark),
+        (x, bottom, mark, -mark),
+        (right, bottom, -mark, -mark),
+    ] {
+        canvas.line(cx, cy, cx + dx, cy, 0.25);
+        canvas.line(cx, cy, cx, cy + dy, 0.25);
+    }
+}
+
+fn draw_qr_code(canvas: &mut dyn Canvas, code: &qrcode::QrCode, x: f64, y: f64, size: f64) {
+    let width = code.width();
+    let module = size / width as f64;
+    for (i, color) in code.to_colors().iter().enumerate() {
+        if *color == qrcode::Color::Dark {
+            let (column, row) = (i % width, i / width);
+            // slightly larger to avoid gaps between the modules
+            canvas.rect(
+                x + column as f64 * module,
+                y + row as f64 * module,
+                module * 1.02,
+                module * 1.02,
+                Color::Black,
+            );
+        }
+    }
+
+    // swiss cross in the center
+    let (cx, cy) = (x + size / 2.0, y + size / 2.0);
+    canvas.rect(cx - 3.5, cy - 3.5, 7.0, 7.0, Color::White);
+    canvas.rect(cx - 3.0, cy - 3.0, 6.0, 6.0, Color::Black);
+    canvas.rect(cx - 0.585, cy - 1.95, 1.17, 3.9, Color::White);
+    canvas.rect(cx - 1.95, cy - 0.585, 3.9, 1.17, Color::White);
+}
+
+/// Render bills as PDF with one A4 page per bill, the payment part at the bottom of the page.
+pub fn render_pdf(bills: &[QrBill]) -> Result<Vec<u8>, QrBillError> {
+    let mut pdf = Pdf::new(210.0, 297.0);
+    for (i, bill) in bills.iter().enumerate() {
+        if i > 0 {
+            pdf.new_page();
+        }
+        bill.draw(&mut pdf, 192.0)?;
+    }
+    Ok(pdf.finish())
+}
+
+/// Create a bill for each person, e.g. for the participants of a camp.
+///
+/// The reference of each bill is made of the group and person id, so payments can be assigned
+/// to the people. QR references are used with QR-IBANs, creditor references otherwise.
+pub fn bills_for_people(
+    group: &Group,
+    people: &[Person],
+    amount: Option<f64>,
+    message: Option<&str>,
+) -> Vec<Result<QrBill, QrBillError>> {
+    let qr_iban = group.bank_account.as_deref().is_some_and(is_qr_iban);
+    people
+        .iter()
+        .map(|person| {
+            let number = format!("{:0>5}{:0>10}", group.id, person.id);
+            let reference = if qr_iban {
+                Reference::Qr(qr_reference(&number)?)
+            } else {
+                Reference::Creditor(creditor_reference(&number)?)
+            };
+            QrBill::for_person(group, person, amount, reference, message)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bill() -> QrBill {
+        QrBill {
+            account: "CH44 3199 9123 0008 8901 2".to_string(),
+            creditor: Address {
+                name: "Pfadi Muster".to_string(),
+                street: Some("Musterstrasse".to_string()),
+                house_number: Some("1".to_string()),
+                zip_code: "3000".to_string(),
+                town: "Bern".to_string(),
+                country: "CH".to_string(),
+            },
+            amount: Some(1250.5),
+            currency: Currency::Chf,
+            debtor: None,
+            reference: Reference::Qr("21 00000 00003 13947 14300 09017".to_string()),
+            message: Some("Sommerlager".to_string()),
+        }
+    }
+
+    #[test]
+    fn references() {
+        assert_eq!(
+            qr_reference("21000000000313947143000901").unwrap(),
+            "210000000003139471430009017"
+        );
+        assert_eq!(qr_reference("1234").unwrap(), "000000000000000000000012347");
+        assert!(is_valid_qr_reference("21 00000 00003 13947 14300 09017"));
+        assert!(!is_valid_qr_reference("21 00000 00003 13947 14300 09018"));
+        assert!(qr_reference("12a").is_err());
+
+        assert_eq!(
+            creditor_reference("539007547034").unwrap(),
+            "RF18539007547034"
+        );
+        assert!(is_valid_creditor_reference("RF18 5390 0754 7034"));
+        assert!(!is_valid_creditor_reference("RF19 5390 0754 7034"));
+    }
+
+    #[test]
+    fn ibans() {
+        assert!(is_valid_iban("CH93 0076 2011 6238 5295 7"));
+        assert!(!is_valid_iban("CH93 0076 2011 6238 5295 8"));
+        assert!(!is_qr_iban("CH93 0076 2011 6238 5295 7"));
+        assert!(is_qr_iban("CH44 3199 9123 0008 8901 2"));
+    }
+
+    #[test]
+    fn validate() {
+        assert_eq!(bill().validate(), Ok(()));
+        let mut b = bill();
+        b.reference = Reference::None;
+        assert_eq!(b.validate(), Err(QrBillError::ReferenceMismatch));
+        b.account = "CH93 0076 2011 6238 5295 7".to_string();
+        assert_eq!(b.validate(), Ok(()));
+        b.amount = Some(0.0);
+        assert_eq!(b.validate(), Err(QrBillError::InvalidAmount(0.0)));
+
+        let mut b = bill();
+        b.message = Some("x".repeat(141));
+        assert_eq!(
+            b.validate(),
+            Err(QrBillError::TooLong {
+                field: "message".to_string(),
+                max: 140
+            })
+        );
+        let mut b = bill();
+        b.creditor.town = "y".repeat(36);
+        assert_eq!(
+            b.validate(),
+            Err(QrBillError::TooLong {
+                field: "creditor town".to_string(),
+                max: 35
+            })
+        );
+    }
+
+    #[test]
+    fn country_codes() {
+        assert_eq!(country_code(None).as_deref(), Some("CH"));
+        assert_eq!(country_code(Some(" ")).as_deref(), Some("CH"));
+        assert_eq!(country_code(Some("li")).as_deref(), Some("LI"));
+        assert_eq!(country_code(Some("Deutschland")), None);
+    }
+
+    #[test]
+    fn payload() {
+        let payload = bill().payload().unwrap();
+        let lines: Vec<&str> = payload.split('\n').collect();
+        assert_eq!(lines.len(), 31);
+        assert_eq!(lines[3], "CH4431999123000889012");
+        assert_eq!(lines[4], "S");
+        assert_eq!(lines[18], "1250.50");
+        assert_eq!(lines[19], "CHF");
+        assert_eq!(lines[27], "QRR");
+        assert_eq!(lines[28], "210000000003139471430009017");
+        assert_eq!(lines[30], "EPD");
+    }
+
+    #[test]
+    fn render() {
+        let svg = bill().to_svg().unwrap();
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains("1 250.50"));
+        assert!(svg.contains("21 00000 00003 13947 14300 09017"));
+        let pdf = render_pdf(&[bill(), bill()]).unwrap();
+        assert!(pdf.starts_with(b"%PDF-1.4"));
+        assert!(pdf.ends_with(b"%%EOF\n"));
+    }
+
+    #[test]
+    fn formatting() {
+        assert_eq!(format_amount(1234567.5), "1 234 567.50");
+        assert_eq!(format_amount(12.0), "12.00");
+        assert_eq!(
+            split_street("Musterweg 12a"),
+            (Some("Musterweg".to_string()), Some("12a".to_string()))
+        );
+        assert_eq!(
+            split_street("Postfach"),
+            (Some("Postfach".to_string()), None)
+        );
+    }
+}
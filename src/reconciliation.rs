@@ -0,0 +1,480 @@
+//! Reconciliation of bank payment notifications (ISO 20022 camt.053/camt.054) with the invoices
+//! loaded from midata.
+
+use crate::midata::{Error, Invoice, MidataConnection};
+use chrono::NaiveDate;
+
+/// amounts differing less than this are considered equal
+const AMOUNT_TOLERANCE: f64 = 0.005;
+
+/// currency of the invoices in midata
+const INVOICE_CURRENCY: &str = "CHF";
+
+/// Errors when reading camt files
+#[derive(Debug)]
+pub enum CamtError {
+    Io(std::io::Error),
+    Xml(roxmltree::Error),
+    /// the document is not a camt.053 or camt.054 document
+    UnknownDocument,
+    /// an entry misses a mandatory element or contains an invalid value
+    InvalidEntry(String),
+}
+
+impl std::fmt::Display for CamtError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            CamtError::Io(e) => write!(f, "Could not read camt file: {}", e),
+            CamtError::Xml(e) => write!(f, "Could not parse camt file: {}", e),
+            CamtError::UnknownDocument => write!(f, "Not a camt.053 or camt.054 document."),
+            CamtError::InvalidEntry(e) => write!(f, "Invalid entry in camt file: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for CamtError {}
+
+impl From<std::io::Error> for CamtError {
+    fn from(e: std::io::Error) -> Self {
+        CamtError::Io(e)
+    }
+}
+
+impl From<roxmltree::Error> for CamtError {
+    fn from(e: roxmltree::Error) -> Self {
+        CamtError::Xml(e)
+    }
+}
+
+/// Incoming payment as notified by the bank
+#[derive(Debug, Clone, PartialEq)]
+pub struct BankPayment {
+    pub amount: f64,
+    pub currency: String,
+    /// QR, ESR or creditor reference given by the debtor
+    pub reference: Option<String>,
+    /// booking date (or value date if not booked yet)
+    pub received_at: Option<NaiveDate>,
+    /// name of the person who payed
+    pub debtor: Option<String>,
+    /// unique reference of the transaction assigned by the bank
+    pub bank_reference: Option<String>,
+}
+
+/// Result of matching a payment with the invoices
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReconciliationStatus {
+    /// the payment settles the open amount of the invoice
+    Matched,
+    /// more than the open amount was payed
+    Overpaid,
+    /// less than the open amount was payed
+    Underpaid,
+    /// no invoice has the reference of the payment
+    UnknownReference,
+    /// the invoice with the reference is not open (draft, payed or cancelled)
+    InvoiceNotOpen,
+    /// the payment is not in the currency of the invoices
+    CurrencyMismatch,
+}
+
+impl ReconciliationStatus {
+    /// true if the payment can be recorded for its invoice
+    pub fn can_be_posted(self) -> bool {
+        matches!(
+            self,
+            ReconciliationStatus::Matched
+                | ReconciliationStatus::Overpaid
+                | ReconciliationStatus::Underpaid
+        )
+    }
+}
+
+/// A payment together with the invoice it was matched to
+#[derive(Debug, Clone)]
+pub struct ReconciliationEntry {
+    pub payment: BankPayment,
+    pub invoice: Option<Invoice>,
+    pub status: ReconciliationStatus,
+    /// payed amount minus the amount that was open before this payment
+    pub difference: f64,
+}
+
+/// Report about matching the payments of camt files with the invoices
+#[derive(Debug, Clone, Default)]
+pub struct ReconciliationReport {
+    pub entries: Vec<ReconciliationEntry>,
+}
+
+fn child<'a, 'input>(
+    node: roxmltree::Node<'a, 'input>,
+    path: &[&str],
+) -> Option<roxmltree::Node<'a, 'input>> {
+    let mut node = node;
+    for name in path {
+        node = node
+            .children()
+            .find(|n| n.is_element() && n.tag_name().name() == *name)?;
+    }
+    Some(node)
+}
+
+fn text(node: roxmltree::Node, path: &[&str]) -> Option<String> {
+    child(node, path)?
+        .text()
+        .map(|text| text.trim().to_string())
+        .filter(|text| !text.is_empty())
+}
+
+fn date(node: roxmltree::Node, path: &[&str]) -> Option<NaiveDate> {
+    // either <Dt>2020-03-31</Dt> or <DtTm>2020-03-31T10:00:00</DtTm>
+    let element = child(node, path)?;
+    let text = text(element, &["Dt"]).or_else(|| text(element, &["DtTm"]))?;
+    NaiveDate::parse_from_str(text.get(..10)?, "%Y-%m-%d").ok()
+}
+
+fn amount(node: roxmltree::Node) -> Result<(f64, String), CamtError> {
+    let value = node.text().unwrap_or_default().trim();
+    let amount = value
+        .parse()
+        .map_err(|_| CamtError::InvalidEntry(format!("invalid amount '{}'", value)))?;
+    let currency = node.attribute("Ccy").unwrap_or_default().to_string();
+    Ok((amount, currency))
+}
+
+/// normalize a payment reference for comparison (whitespace is not significant)
+fn normalize_reference(reference: &str) -> String {
+    reference
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_uppercase()
+}
+
+/// Read the incoming payments of a camt.053 (statement) or camt.054 (notification) document.
+///
+/// # Note
+/// Outgoing payments (debits) and reversals are ignored.
+pub fn parse_camt(xml: &str) -> Result<Vec<BankPayment>, CamtError> {
+    let document = roxmltree::Document::parse(xml)?;
+    let root = document.root_element();
+    if child(root, &["BkToCstmrDbtCdtNtfctn"]).is_none()
+        && child(root, &["BkToCstmrStmt"]).is_none()
+    {
+        return Err(CamtError::UnknownDocument);
+    }
+
+    let mut payments: Vec<BankPayment> = vec![];
+    for entry in root
+        .descendants()
+        .filter(|n| n.is_element() && n.tag_name().name() == "Ntry")
+    {
+        let is_credit = text(entry, &["CdtDbtInd"]).as_deref() == Some("CRDT");
+        let is_reversal = text(entry, &["RvslInd"]).as_deref() == Some("true");
+        if !is_credit || is_reversal {
+            continue;
+        }
+        let received_at = date(entry, &["BookgDt"]).or_else(|| date(entry, &["ValDt"]));
+
+        let transactions: Vec<roxmltree::Node> = child(entry, &["NtryDtls"])
+            .into_iter()
+            .flat_map(|details| details.descendants())
+            .filter(|n| n.is_element() && n.tag_name().name() == "TxDtls")
+            .collect();
+
+        if transactions.is_empty() {
+            let amount_node = child(entry, &["Amt"])
+                .ok_or_else(|| CamtError::InvalidEntry("entry without amount".to_string()))?;
+            let (amount, currency) = amount(amount_node)?;
+            payments.push(BankPayment {
+                amount,
+                currency,
+                reference: None,
+                received_at,
+                debtor: None,
+                bank_reference: text(entry, &["AcctSvcrRef"]),
+            });
+            continue;
+        }
+
+        for transaction in &transactions {
+            if text(*transaction, &["CdtDbtInd"]).as_deref() == Some("DBIT") {
+                continue;
+            }
+            let amount_node = child(*transaction, &["Amt"])
+                .or_else(|| child(*transaction, &["AmtDtls", "TxAmt", "Amt"]))
+                .or_else(|| {
+                    // the amount of the entry is the amount of its only transaction
+                    if transactions.len() == 1 {
+                        child(entry, &["Amt"])
+                    } else {
+                        None
+                    }
+                })
+                .ok_or_else(|| CamtError::InvalidEntry("transaction without amount".to_string()))?;
+            let (amount, currency) = amount(amount_node)?;
+            payments.push(BankPayment {
+                amount,
+                currency,
+                reference: text(*transaction, &["RmtInf", "Strd", "CdtrRefInf", "Ref"]),
+                received_at,
+                debtor: text(*transaction, &["RltdPties", "Dbtr", "Nm"])
+                    .or_else(|| text(*transaction, &["RltdPties", "Dbtr", "Pty", "Nm"])),
+                bank_reference: text(*transaction, &["Refs", "AcctSvcrRef"])
+                    .or_else(|| text(entry, &["AcctSvcrRef"])),
+            });
+        }
+    }
+    Ok(payments)
+}
+
+/// Read the incoming payments of a camt file. See parse_camt().
+pub fn read_camt_file<P: AsRef<std::path::Path>>(path: P) -> Result<Vec<BankPayment>, CamtError> {
+    parse_camt(&std::fs::read_to_string(path)?)
+}
+
+/// Check if the invoice has the reference, either as QR or as ESR reference.
+fn has_reference(invoice: &Invoice, reference: &str) -> bool {
+    [&invoice.reference, &invoice.esr_number]
+        .iter()
+        .filter_map(|r| r.as_deref())
+        .any(|r| normalize_reference(r) == reference)
+}
+
+/// Match payments with invoices by their QR or ESR reference.
+///
+/// # Note
+/// Several payments for the same invoice are reconciled in the given order, each against the
+/// amount still open after the previous ones. Payments are only matched with open invoices and
+/// must be in the currency of the invoices (CHF); other payments get a status which prevents
+/// them from being posted.
+pub fn reconcile(payments: Vec<BankPayment>, invoices: &[Invoice]) -> ReconciliationReport {
+    let mut open_amounts: Vec<f64> = invoices.iter().map(|invoice| invoice.amount_open).collect();
+    let mut report = ReconciliationReport::default();
+
+    for payment in payments {
+        let reference = payment.reference.as_deref().map(normalize_reference);
+        let matching = |open: bool| {
+            let reference = reference.as_ref()?;
+            invoices
+                .iter()
+                .position(|invoice| invoice.is_open() == open && has_reference(invoice, reference))
+        };
+
+        let entry = match (matching(true), matching(false)) {
+            (Some(index), _) if payment.currency != INVOICE_CURRENCY => ReconciliationEntry {
+                difference: payment.amount,
+                payment,
+                invoice: Some(invoices[index].clone()),
+                status: ReconciliationStatus::CurrencyMismatch,
+            },
+            (Some(index), _) => {
+                let difference = payment.amount - open_amounts[index];
+                open_amounts[index] = (open_amounts[index] - payment.amount).max(0.0);
+                let status = if difference.abs() < AMOUNT_TOLERANCE {
+                    ReconciliationStatus::Matched
+                } else if difference > 0.0 {
+                    ReconciliationStatus::Overpaid
+                } else {
+                    ReconciliationStatus::Underpaid
+                };
+                ReconciliationEntry {
+                    payment,
+                    invoice: Some(invoices[index].clone()),
+                    status,
+                    difference,
+                }
+            }
+            (None, Some(index)) => ReconciliationEntry {
+                difference: payment.amount,
+                payment,
+                invoice: Some(invoices[index].clone()),
+                status: ReconciliationStatus::InvoiceNotOpen,
+            },
+            (None, None) => ReconciliationEntry {
+                difference: payment.amount,
+                payment,
+                invoice: None,
+                status: ReconciliationStatus::UnknownReference,
+            },
+        };
+        report.entries.push(entry);
+    }
+    report
+}
+
+impl ReconciliationReport {
+    /// entries with the given status
+    pub fn with_status(
+        &self,
+        status: ReconciliationStatus,
+    ) -> impl Iterator<Item = &ReconciliationEntry> {
+        self.entries
+            .iter()
+            .filter(move |entry| entry.status == status)
+    }
+
+    /// Record the payments matched to an invoice in midata.
+    ///
+    /// # Note
+    /// Only payments matched to an open invoice in its currency are recorded, see
+    /// ReconciliationStatus::can_be_posted(). Payments already recorded for the invoice with the
+    /// same bank reference are skipped, so a file can be imported twice. Payments without a date
+    /// are recorded as received today.
+    pub fn post_payments<'a>(
+        &'a self,
+        connection: &MidataConnection,
+    ) -> Vec<(&'a ReconciliationEntry, Result<(), Error>)> {
+        let mut results = vec![];
+        for entry in &self.entries {
+            let invoice = match &entry.invoice {
+                Some(invoice) if entry.status.can_be_posted() => invoice,
+                _ => continue,
+            };
+            let bank_reference = entry.payment.bank_reference.as_deref();
+            if bank_reference.is_some()
+                && invoice
+                    .payments
+                    .iter()
+                    .any(|payment| payment.reference.as_deref() == bank_reference)
+            {
+                continue;
+            }
+            let received_at = entry
+                .payment
+                .received_at
+                .unwrap_or_else(|| chrono::Local::now().date_naive());
+            let result = connection.create_payment(
+                invoice,
+                entry.payment.amount,
+                received_at,
+                bank_reference,
+            );
+            results.push((entry, result));
+        }
+        results
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invoices() -> Vec<Invoice> {
+        serde_json::from_str(
+            r#"[
+                {"id": "1", "title": "Mitgliederbeitrag", "state": "issued",
+                 "reference": "21 00000 00003 13947 14300 09017",
+                 "total": "50.0", "amount_paid": "0.0", "amount_open": "50.0"},
+                {"id": "2", "title": "Mitgliederbeitrag", "state": "sent",
+                 "esr_number": "00 00376 80338 90000 00000 00021",
+                 "total": "50.0", "amount_paid": "20.0", "amount_open": "30.0"},
+                {"id": "3", "title": "Lagerbeitrag", "state": "reminded",
+                 "reference": "RF18539007547034",
+                 "total": 120.0, "amount_paid": 0.0, "amount_open": 120.0}
+            ]"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn parse_camt054() {
+        let payments = parse_camt(include_str!("../tests/data/camt054.xml")).unwrap();
+        assert_eq!(payments.len(), 3);
+        assert_eq!(payments[0].amount, 50.0);
+        assert_eq!(payments[0].currency, "CHF");
+        assert_eq!(
+            payments[0].reference.as_deref(),
+            Some("210000000003139471430009017")
+        );
+        assert_eq!(payments[0].debtor.as_deref(), Some("Muster Hans"));
+        assert_eq!(
+            payments[0].received_at,
+            Some(NaiveDate::from_ymd_opt(2020, 3, 31).unwrap())
+        );
+        assert_eq!(
+            payments[2].reference.as_deref(),
+            Some("999999999999999999999999999")
+        );
+    }
+
+    #[test]
+    fn parse_camt053_ignores_debits() {
+        let payments = parse_camt(include_str!("../tests/data/camt053.xml")).unwrap();
+        assert_eq!(payments.len(), 1);
+        assert_eq!(payments[0].amount, 130.0);
+        assert_eq!(
+            payments[0].bank_reference.as_deref(),
+            Some("ZV20200401/0003")
+        );
+    }
+
+    #[test]
+    fn parse_unknown_document() {
+        assert!(matches!(
+            parse_camt("<Document><Other/></Document>"),
+            Err(CamtError::UnknownDocument)
+        ));
+    }
+
+    #[test]
+    fn reconcile_payments() {
+        let mut payments = parse_camt(include_str!("../tests/data/camt054.xml")).unwrap();
+        payments.append(&mut parse_camt(include_str!("../tests/data/camt053.xml")).unwrap());
+        let report = reconcile(payments, &invoices());
+
+        let statuses: Vec<ReconciliationStatus> =
+            report.entries.iter().map(|entry| entry.status).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                ReconciliationStatus::Matched,
+                ReconciliationStatus::Underpaid,
+                ReconciliationStatus::UnknownReference,
+                ReconciliationStatus::Overpaid,
+            ]
+        );
+        assert_eq!(report.entries[1].invoice.as_ref().unwrap().id, "2");
+        assert!((report.entries[1].difference + 5.0).abs() < AMOUNT_TOLERANCE);
+        assert!((report.entries[3].difference - 10.0).abs() < AMOUNT_TOLERANCE);
+        assert_eq!(
+            report
+                .with_status(ReconciliationStatus::UnknownReference)
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn reconcile_closed_invoices_and_currencies() {
+        let mut invoices = invoices();
+        invoices[0].state = crate::midata::InvoiceState::Payed;
+        let payment = |reference: &str, currency: &str| BankPayment {
+            amount: 50.0,
+            currency: currency.to_string(),
+            reference: Some(reference.to_string()),
+            received_at: None,
+            debtor: None,
+            bank_reference: None,
+        };
+        let payments = vec![
+            payment("210000000003139471430009017", "CHF"),
+            payment("RF18539007547034", "EUR"),
+        ];
+        let report = reconcile(payments, &invoices);
+
+        let statuses: Vec<ReconciliationStatus> =
+            report.entries.iter().map(|entry| entry.status).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                ReconciliationStatus::InvoiceNotOpen,
+                ReconciliationStatus::CurrencyMismatch,
+            ]
+        );
+        assert!(report
+            .entries
+            .iter()
+            .all(|entry| !entry.status.can_be_posted()));
+    }
+}
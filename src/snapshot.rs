@@ -0,0 +1,467 @@
+//! Snapshots of a group tree with its people and the differences between two snapshots, e.g. to
+//! tell who joined or left the Abteilung since last month.
+
+use crate::midata::{display_name, Group, MidataConnection, Person};
+use chrono::{DateTime, Utc};
+
+/// Group as stored in a snapshot
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct SnapshotGroup {
+    pub id: String,
+    pub name: String,
+    pub group_type: String,
+    /// None for the root group of the snapshot
+    pub parent_id: Option<String>,
+}
+
+/// Role as stored in a snapshot
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct SnapshotRole {
+    pub id: String,
+    pub role_type: String,
+    pub group_id: String,
+    pub group_name: String,
+}
+
+/// Person as stored in a snapshot
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct SnapshotPerson {
+    pub id: String,
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub nickname: Option<String>,
+    pub email: Option<String>,
+    pub address: Option<String>,
+    pub zip_code: Option<String>,
+    pub town: Option<String>,
+    pub country: Option<String>,
+    pub roles: Vec<SnapshotRole>,
+}
+
+impl SnapshotPerson {
+    /// first and last name followed by the scout's name, e.g. "Anna Muster / Fuchs"
+    pub fn name(&self) -> String {
+        display_name(
+            self.first_name.as_deref(),
+            self.last_name.as_deref(),
+            self.nickname.as_deref(),
+        )
+    }
+
+    /// postal address on one line, e.g. "Musterweg 1, 3000 Bern"
+    pub fn postal_address(&self) -> String {
+        let town: Vec<&str> = [&self.zip_code, &self.town]
+            .iter()
+            .filter_map(|part| part.as_deref())
+            .collect();
+        let town = town.join(" ");
+        [
+            self.address.as_deref(),
+            Some(town.as_str()),
+            self.country.as_deref(),
+        ]
+        .iter()
+        .flatten()
+        .filter(|part| !part.is_empty())
+        .copied()
+        .collect::<Vec<&str>>()
+        .join(", ")
+    }
+}
+
+/// Group tree with its people at a point in time
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct Snapshot {
+    pub taken_at: DateTime<Utc>,
+    pub groups: Vec<SnapshotGroup>,
+    pub people: Vec<SnapshotPerson>,
+}
+
+/// Difference between two snapshots
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum MembershipChange {
+    PersonJoined(SnapshotPerson),
+    PersonLeft(SnapshotPerson),
+    RoleAdded {
+        person: SnapshotPerson,
+        role: SnapshotRole,
+    },
+    RoleRemoved {
+        person: SnapshotPerson,
+        role: SnapshotRole,
+    },
+    /// the role kept its id but changed its type
+    RoleChanged {
+        person: SnapshotPerson,
+        before: SnapshotRole,
+        after: SnapshotRole,
+    },
+    AddressChanged {
+        person: SnapshotPerson,
+        before: String,
+        after: String,
+    },
+    EmailChanged {
+        person: SnapshotPerson,
+        before: Option<String>,
+        after: Option<String>,
+    },
+    GroupAdded(SnapshotGroup),
+    GroupRenamed {
+        group: SnapshotGroup,
+        before: String,
+    },
+    GroupRemoved(SnapshotGroup),
+}
+
+impl std::fmt::Display for MembershipChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            MembershipChange::PersonJoined(person) => {
+                let roles: Vec<String> = person
+                    .roles
+                    .iter()
+                    .map(|role| format!("{} in {}", role.role_type, role.group_name))
+                    .collect();
+                write!(f, "{} joined as {}", person.name(), roles.join(", "))
+            }
+            MembershipChange::PersonLeft(person) => write!(f, "{} left", person.name()),
+            MembershipChange::RoleAdded { person, role } => write!(
+                f,
+                "{} is now {} in {}",
+                person.name(),
+                role.role_type,
+                role.group_name
+            ),
+            MembershipChange::RoleRemoved { person, role } => write!(
+                f,
+                "{} is no longer {} in {}",
+                person.name(),
+                role.role_type,
+                role.group_name
+            ),
+            MembershipChange::RoleChanged {
+                person,
+                before,
+                after,
+            } => write!(
+                f,
+                "{} changed from {} to {} in {}",
+                person.name(),
+                before.role_type,
+                after.role_type,
+                after.group_name
+            ),
+            MembershipChange::AddressChanged {
+                person,
+                before,
+                after,
+            } => write!(f, "{} moved from {} to {}", person.name(), before, after),
+            MembershipChange::EmailChanged {
+                person,
+                before,
+                after,
+            } => write!(
+                f,
+                "{} changed the email from {} to {}",
+                person.name(),
+                before.as_deref().unwrap_or("-"),
+                after.as_deref().unwrap_or("-")
+            ),
+            MembershipChange::GroupAdded(group) => write!(f, "new group {}", group.name),
+            MembershipChange::GroupRenamed { group, before } => {
+                write!(f, "group {} was renamed to {}", before, group.name)
+            }
+            MembershipChange::GroupRemoved(group) => write!(f, "group {} was removed", group.name),
+        }
+    }
+}
+
+/// Changes between two snapshots, see Snapshot::diff()
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Changelog {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub changes: Vec<MembershipChange>,
+}
+
+impl std::fmt::Display for Changelog {
+    /// one change per line
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for change in &self.changes {
+            writeln!(f, "{}", change)?;
+        }
+        Ok(())
+    }
+}
+
+fn snapshot_group(group: &Group, parent_id: Option<String>) -> SnapshotGroup {
+    SnapshotGroup {
+        id: group.id.clone(),
+        name: group.name.clone(),
+        group_type: group.group_type().class_name().to_string(),
+        parent_id,
+    }
+}
+
+impl Snapshot {
+    /// Take a snapshot of a group, all groups below it and their people.
+    ///
+    /// # Arguments
+    /// connection: connection to load the groups and people with
+    /// id: id of the root group, e.g. of an Abteilung
+    pub fn take(connection: &MidataConnection, id: u16) -> Snapshot {
+        let taken_at = Utc::now();
+        let root = connection.load_group(id);
+        let mut groups = vec![snapshot_group(&root, None)];
+        let mut parents = vec![root];
+        while !parents.is_empty() {
+            let mut child_ids: Vec<u16> = vec![];
+            for parent in &parents {
+                for child in &parent.chilrden {
+                    groups.push(snapshot_group(child, Some(parent.id.clone())));
+                    child_ids.extend(child.id.parse::<u16>().ok());
+                }
+            }
+            parents = if child_ids.is_empty() {
+                vec![]
+            } else {
+                connection.load_groups(child_ids)
+            };
+        }
+
+        let ids: Vec<u16> = groups.iter().filter_map(|g| g.id.parse().ok()).collect();
+        let people = connection.load_people_of_groups(ids);
+        Snapshot::of(taken_at, groups, &people)
+    }
+
+    /// Create a snapshot from groups and their people. People listed several times are merged.
+    pub fn of(taken_at: DateTime<Utc>, groups: Vec<SnapshotGroup>, people: &[Person]) -> Snapshot {
+        let mut snapshot_people: Vec<SnapshotPerson> = vec![];
+        for person in people {
+            let roles = person.roles.iter().filter_map(|role| {
+                let group_id = &role.links.as_ref()?.group;
+                let group = groups.iter().find(|group| &group.id == group_id)?;
+                Some(SnapshotRole {
+                    id: role.id.clone(),
+                    role_type: role.role_type.clone(),
+                    group_id: group.id.clone(),
+                    group_name: group.name.clone(),
+                })
+            });
+            let index = match snapshot_people.iter().position(|p| p.id == person.id) {
+                Some(index) => index,
+                None => {
+                    snapshot_people.push(SnapshotPerson {
+                        id: person.id.clone(),
+                        first_name: person.first_name.clone(),
+                        last_name: person.last_name.clone(),
+                        nickname: person.nickname.clone(),
+                        email: person.email.clone(),
+                        address: person.address.clone(),
+                        zip_code: person.zip_code.clone(),
+                        town: person.town.clone(),
+                        country: person.country.clone(),
+                        roles: vec![],
+                    });
+                    snapshot_people.len() - 1
+                }
+            };
+            let snapshot_person = &mut snapshot_people[index];
+            for role in roles {
+                if !snapshot_person.roles.iter().any(|r| r.id == role.id) {
+                    snapshot_person.roles.push(role);
+                }
+            }
+        }
+        snapshot_people.sort_by(|a, b| a.id.cmp(&b.id));
+        Snapshot {
+            taken_at,
+            groups,
+            people: snapshot_people,
+        }
+    }
+
+    /// the snapshot as JSON, see from_json()
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("a snapshot can always be serialized")
+    }
+
+    /// read a snapshot written by to_json()
+    pub fn from_json(json: &str) -> Result<Snapshot, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    /// Changes from this (older) snapshot to a newer one.
+    ///
+    /// # Note
+    /// Group changes come first, followed by the changes of the people ordered by their id.
+    pub fn diff(&self, newer: &Snapshot) -> Changelog {
+        let mut changes: Vec<MembershipChange> = vec![];
+
+        for group in &newer.groups {
+            match self.groups.iter().find(|old| old.id == group.id) {
+                None => changes.push(MembershipChange::GroupAdded(group.clone())),
+                Some(old) if old.name != group.name => {
+                    changes.push(MembershipChange::GroupRenamed {
+                        group: group.clone(),
+                        before: old.name.clone(),
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+        for group in &self.groups {
+            if !newer.groups.iter().any(|new| new.id == group.id) {
+                changes.push(MembershipChange::GroupRemoved(group.clone()));
+            }
+        }
+
+        let mut people: Vec<(Option<&SnapshotPerson>, Option<&SnapshotPerson>)> = vec![];
+        for person in &self.people {
+            people.push((
+                Some(person),
+                newer.people.iter().find(|new| new.id == person.id),
+            ));
+        }
+        for person in &newer.people {
+            if !self.people.iter().any(|old| old.id == person.id) {
+                people.push((None, Some(person)));
+            }
+        }
+        people.sort_by_key(|(old, new)| old.or(*new).map(|person| person.id.clone()));
+
+        for (old, new) in people {
+            let (old, new) = match (old, new) {
+                (Some(old), Some(new)) => (old, new),
+                (Some(old), None) => {
+                    changes.push(MembershipChange::PersonLeft(old.clone()));
+                    continue;
+                }
+                (None, Some(new)) => {
+                    changes.push(MembershipChange::PersonJoined(new.clone()));
+                    continue;
+                }
+                (None, None) => continue,
+            };
+
+            for role in &new.roles {
+                match old.roles.iter().find(|r| r.id == role.id) {
+                    None => changes.push(MembershipChange::RoleAdded {
+                        person: new.clone(),
+                        role: role.clone(),
+                    }),
+                    Some(before) if before.role_type != role.role_type => {
+                        changes.push(MembershipChange::RoleChanged {
+                            person: new.clone(),
+                            before: before.clone(),
+                            after: role.clone(),
+                        })
+                    }
+                    Some(_) => {}
+                }
+            }
+            for role in &old.roles {
+                if !new.roles.iter().any(|r| r.id == role.id) {
+                    changes.push(MembershipChange::RoleRemoved {
+                        person: new.clone(),
+                        role: role.clone(),
+                    });
+                }
+            }
+            if old.postal_address() != new.postal_address() {
+                changes.push(MembershipChange::AddressChanged {
+                    person: new.clone(),
+                    before: old.postal_address(),
+                    after: new.postal_address(),
+                });
+            }
+            if old.email != new.email {
+                changes.push(MembershipChange::EmailChanged {
+                    person: new.clone(),
+                    before: old.email.clone(),
+                    after: new.email.clone(),
+                });
+            }
+        }
+
+        Changelog {
+            from: self.taken_at,
+            to: newer.taken_at,
+            changes,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(json: &str) -> Snapshot {
+        Snapshot::from_json(json).unwrap()
+    }
+
+    #[test]
+    fn diff_snapshots() {
+        let old = snapshot(
+            r#"{"taken_at": "2024-02-01T00:00:00Z",
+                "groups": [
+                    {"id": "1", "name": "Pfadi Muster", "group_type": "Group::Abteilung",
+                     "parent_id": null},
+                    {"id": "2", "name": "Meute Akela", "group_type": "Group::Woelfe",
+                     "parent_id": "1"},
+                    {"id": "3", "name": "Trupp Falke", "group_type": "Group::Pfadi",
+                     "parent_id": "1"}
+                ],
+                "people": [
+                    {"id": "10", "first_name": "Anna", "last_name": "Muster", "nickname": null,
+                     "email": "anna@example.com", "address": "Weg 1", "zip_code": "3000",
+                     "town": "Bern", "country": null,
+                     "roles": [{"id": "100", "role_type": "Wolf", "group_id": "2",
+                                "group_name": "Meute Akela"}]},
+                    {"id": "11", "first_name": "Ben", "last_name": null, "nickname": null,
+                     "email": null, "address": null, "zip_code": null, "town": null,
+                     "country": null, "roles": []}
+                ]}"#,
+        );
+        let new = snapshot(
+            r#"{"taken_at": "2024-03-01T00:00:00Z",
+                "groups": [
+                    {"id": "1", "name": "Pfadi Muster", "group_type": "Group::Abteilung",
+                     "parent_id": null},
+                    {"id": "2", "name": "Meute Baloo", "group_type": "Group::Woelfe",
+                     "parent_id": "1"}
+                ],
+                "people": [
+                    {"id": "10", "first_name": "Anna", "last_name": "Muster", "nickname": null,
+                     "email": "anna@example.org", "address": "Weg 2", "zip_code": "3000",
+                     "town": "Bern", "country": null,
+                     "roles": [{"id": "101", "role_type": "Leitwolf", "group_id": "2",
+                                "group_name": "Meute Baloo"}]},
+                    {"id": "12", "first_name": "Clara", "last_name": null, "nickname": "Eule",
+                     "email": null, "address": null, "zip_code": null, "town": null,
+                     "country": null,
+                     "roles": [{"id": "102", "role_type": "Wolf", "group_id": "2",
+                                "group_name": "Meute Baloo"}]}
+                ]}"#,
+        );
+
+        let changelog = old.diff(&new);
+        let lines: Vec<String> = changelog.changes.iter().map(|c| c.to_string()).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "group Meute Akela was renamed to Meute Baloo",
+                "group Trupp Falke was removed",
+                "Anna Muster is now Leitwolf in Meute Baloo",
+                "Anna Muster is no longer Wolf in Meute Akela",
+                "Anna Muster moved from Weg 1, 3000 Bern to Weg 2, 3000 Bern",
+                "Anna Muster changed the email from anna@example.com to anna@example.org",
+                "Ben left",
+                "Clara / Eule joined as Wolf in Meute Baloo",
+            ]
+        );
+        assert_eq!(Snapshot::from_json(&new.to_json()).unwrap(), new);
+        assert!(new.diff(&new).changes.is_empty());
+    }
+}
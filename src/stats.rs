@@ -0,0 +1,547 @@
+//! Membership statistics of a group tree, e.g. for the annual report to the Kantonalverband.
+//!
+//! The statistics of a group cover the group and all groups below it. People are counted once
+//! per group even if they have several roles in it. Participants have a participant role
+//! (Role::is_tn()), the other people with a role are counted as leaders (midata::is_leader()).
+
+use crate::export::{csv_writer, CsvOptions, ExportError};
+use crate::graph::GroupTree;
+use crate::midata::{is_leader, GroupType, MidataConnection, Person, Role};
+use chrono::{Datelike, NaiveDate};
+use std::collections::{HashMap, HashSet};
+
+/// Participants and leaders in the groups of a stufe
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct StufeStats {
+    /// class name of the group type, e.g. `Group::Woelfe`
+    pub stufe: String,
+    pub participants: usize,
+    pub leaders: usize,
+}
+
+/// Number of people by gender as stored in midata
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct GenderCounts {
+    /// gender "w"
+    pub female: usize,
+    /// gender "m"
+    pub male: usize,
+    /// gender not given
+    pub unknown: usize,
+}
+
+impl GenderCounts {
+    fn add(&mut self, gender: Option<&str>) {
+        match gender {
+            Some("w") => self.female += 1,
+            Some("m") => self.male += 1,
+            _ => self.unknown += 1,
+        }
+    }
+}
+
+/// Number of people of an age, one bar of the age pyramid
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct AgeCount {
+    /// age in years
+    pub age: u32,
+    pub genders: GenderCounts,
+}
+
+/// People entering and leaving in a year
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct YearStats {
+    pub year: i32,
+    /// people whose first role in the group tree started in the year
+    pub entries: usize,
+    /// people without current role whose last role in the group tree ended in the year
+    pub leavers: usize,
+}
+
+/// Statistics of a group and the groups below it
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct GroupStats {
+    pub group_id: String,
+    pub group_name: String,
+    /// class name of the group type, e.g. `Group::Abteilung`
+    pub group_type: String,
+    pub participants: usize,
+    pub leaders: usize,
+    /// participants and leaders
+    pub total: usize,
+    /// leaders per participant, None without participants
+    pub leader_ratio: Option<f64>,
+    /// stufen with participants or leaders
+    pub stufen: Vec<StufeStats>,
+    pub genders: GenderCounts,
+    /// age pyramid, sorted by age
+    pub ages: Vec<AgeCount>,
+    pub without_birthday: usize,
+    /// entries and leavers, sorted by year
+    pub years: Vec<YearStats>,
+    pub children: Vec<GroupStats>,
+}
+
+/// entries and leavers of a year, added if missing
+fn year_stats(years: &mut Vec<YearStats>, year: i32) -> &mut YearStats {
+    match years.iter().position(|y| y.year == year) {
+        Some(index) => &mut years[index],
+        None => {
+            years.push(YearStats {
+                year,
+                entries: 0,
+                leavers: 0,
+            });
+            years.last_mut().unwrap()
+        }
+    }
+}
+
+const STUFEN: [GroupType; 6] = [
+    GroupType::Biber,
+    GroupType::Woelfe,
+    GroupType::Pfadi,
+    GroupType::Pio,
+    GroupType::Rover,
+    GroupType::Pta,
+];
+
+/// ids and types of the groups of a tree
+fn groups_of(tree: &GroupTree, groups: &mut Vec<(String, GroupType)>) {
+    groups.push((tree.group.id.clone(), tree.group.group_type()));
+    for child in &tree.children {
+        groups_of(child, groups);
+    }
+}
+
+/// people with all their roles, people listed several times are merged
+fn unique_people(people: &[Person]) -> Vec<(&Person, Vec<&Role>)> {
+    let mut unique: Vec<(&Person, Vec<&Role>)> = vec![];
+    let mut indexes: HashMap<&str, usize> = HashMap::new();
+    let mut roles: HashSet<(&str, &str)> = HashSet::new();
+    for person in people {
+        let index = *indexes.entry(person.id.as_str()).or_insert_with(|| {
+            unique.push((person, vec![]));
+            unique.len() - 1
+        });
+        for role in &person.roles {
+            if roles.insert((person.id.as_str(), role.id.as_str())) {
+                unique[index].1.push(role);
+            }
+        }
+    }
+    unique
+}
+
+impl GroupStats {
+    /// Compute the statistics of a group tree.
+    ///
+    /// # Arguments
+    /// tree: groups to compute the statistics of
+    /// people: people of the groups with their roles, e.g. loaded with
+    ///     MidataConnection::load_people_of_groups(). Roles in other groups are ignored.
+    /// on: day to compute the ages and the current roles on
+    ///
+    /// # Note
+    /// Leavers are only known if the ended roles of the people were loaded, see
+    /// MidataConnection::load_role_history().
+    pub fn compute(tree: &GroupTree, people: &[Person], on: NaiveDate) -> GroupStats {
+        GroupStats::of(tree, &unique_people(people), on)
+    }
+
+    /// Load a group tree with its people and compute its statistics, see compute().
+    pub fn load(connection: &MidataConnection, id: u16, on: NaiveDate) -> GroupStats {
+        let tree = GroupTree::load(connection, id, None);
+        let mut groups = vec![];
+        groups_of(&tree, &mut groups);
+        let ids: Vec<u16> = groups
+            .iter()
+            .filter_map(|(id, _)| id.parse().ok())
+            .collect();
+        let people = connection.load_people_of_groups(ids);
+        GroupStats::compute(&tree, &people, on)
+    }
+
+    fn of(tree: &GroupTree, people: &[(&Person, Vec<&Role>)], on: NaiveDate) -> GroupStats {
+        let mut groups = vec![];
+        groups_of(tree, &mut groups);
+        let group_type = |role: &Role| {
+            let id = &role.links.as_ref()?.group;
+            groups.iter().find(|(g, _)| g == id).map(|(_, t)| t)
+        };
+
+        let mut stats = GroupStats {
+            group_id: tree.group.id.clone(),
+            group_name: tree.group.name.clone(),
+            group_type: tree.group.group_type().class_name().to_string(),
+            participants: 0,
+            leaders: 0,
+            total: 0,
+            leader_ratio: None,
+            stufen: vec![],
+            genders: GenderCounts::default(),
+            ages: vec![],
+            without_birthday: 0,
+            years: vec![],
+            children: vec![],
+        };
+        let mut stufen: Vec<StufeStats> = STUFEN
+            .iter()
+            .map(|stufe| StufeStats {
+                stufe: stufe.class_name().to_string(),
+                participants: 0,
+                leaders: 0,
+            })
+            .collect();
+        let mut members: Vec<&Person> = vec![];
+        for (person, roles) in people {
+            let roles: Vec<&Role> = roles
+                .iter()
+                .filter(|role| group_type(role).is_some())
+                .copied()
+                .collect();
+            if roles.is_empty() {
+                continue;
+            }
+            let active: Vec<&Role> = roles
+                .iter()
+                .filter(|role| role.is_active_on(on))
+                .copied()
+                .collect();
+
+            if let Some(entry) = roles.iter().filter_map(|r| r.start()).min() {
+                year_stats(&mut stats.years, entry.year()).entries += 1;
+            }
+            if active.is_empty() {
+                let deleted = roles.iter().filter_map(|r| r.end());
+                if let Some(leaver) = deleted.max() {
+                    year_stats(&mut stats.years, leaver.year()).leavers += 1;
+                }
+                continue;
+            }
+
+            members.push(person);
+            if is_leader(active.iter().copied()) {
+                stats.leaders += 1;
+            } else {
+                stats.participants += 1;
+            }
+            for (stufe, counts) in STUFEN.iter().zip(stufen.iter_mut()) {
+                let roles: Vec<&&Role> = active
+                    .iter()
+                    .filter(|role| group_type(role) == Some(stufe))
+                    .collect();
+                if is_leader(roles.iter().map(|role| **role)) {
+                    counts.leaders += 1;
+                } else if !roles.is_empty() {
+                    counts.participants += 1;
+                }
+            }
+        }
+
+        for person in &members {
+            stats.genders.add(person.gender.as_deref());
+            match person
+                .birthday
+                .and_then(|birthday| on.years_since(birthday))
+            {
+                Some(age) => match stats.ages.iter_mut().find(|a| a.age == age) {
+                    Some(count) => count.genders.add(person.gender.as_deref()),
+                    None => {
+                        let mut genders = GenderCounts::default();
+                        genders.add(person.gender.as_deref());
+                        stats.ages.push(AgeCount { age, genders });
+                    }
+                },
+                None => stats.without_birthday += 1,
+            }
+        }
+        stats.total = members.len();
+        if stats.participants > 0 {
+            stats.leader_ratio = Some(stats.leaders as f64 / stats.participants as f64);
+        }
+        stats.stufen = stufen
+            .into_iter()
+            .filter(|stufe| stufe.participants + stufe.leaders > 0)
+            .collect();
+        stats.ages.sort_by_key(|age| age.age);
+        stats.years.sort_by_key(|year| year.year);
+        stats.children = tree
+            .children
+            .iter()
+            .map(|child| GroupStats::of(child, people, on))
+            .collect();
+        stats
+    }
+
+    /// Statistics as JSON
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("statistics can always be serialized")
+    }
+
+    /// the group and all groups below it with their depth, depth first
+    fn flatten(&self, depth: usize) -> Vec<(usize, &GroupStats)> {
+        let mut groups = vec![(depth, self)];
+        for child in &self.children {
+            groups.append(&mut child.flatten(depth + 1));
+        }
+        groups
+    }
+
+    /// Write the statistics as CSV with one line per group.
+    ///
+    /// The file is formatted for Excel like export::write_csv() with default options. Besides
+    /// the counts, there are columns for participants and leaders of each stufe as well as
+    /// entries and leavers of each year. The age pyramid is only part of the JSON.
+    pub fn write_csv<W: std::io::Write>(&self, writer: W) -> Result<(), ExportError> {
+        let groups = self.flatten(0);
+        let mut years: Vec<i32> = groups
+            .iter()
+            .flat_map(|(_, group)| group.years.iter().map(|year| year.year))
+            .collect();
+        years.sort_unstable();
+        years.dedup();
+        let stufen: Vec<String> = STUFEN
+            .iter()
+            .map(|stufe| {
+                stufe
+                    .class_name()
+                    .trim_start_matches("Group::")
+                    .to_lowercase()
+            })
+            .collect();
+
+        let mut csv = csv_writer(writer, &CsvOptions::default())?;
+        let mut header: Vec<String> = [
+            "depth",
+            "group_id",
+            "group",
+            "group_type",
+            "participants",
+            "leaders",
+            "total",
+            "leader_ratio",
+            "female",
+            "male",
+            "unknown_gender",
+            "without_birthday",
+        ]
+        .iter()
+        .map(|column| column.to_string())
+        .collect();
+        for stufe in &stufen {
+            header.push(format!("participants_{}", stufe));
+            header.push(format!("leaders_{}", stufe));
+        }
+        for year in &years {
+            header.push(format!("entries_{}", year));
+            header.push(format!("leavers_{}", year));
+        }
+        csv.write_record(&header)?;
+
+        for (depth, group) in groups {
+            let mut record: Vec<String> = vec![
+                depth.to_string(),
+                group.group_id.clone(),
+                group.group_name.clone(),
+                group.group_type.clone(),
+                group.participants.to_string(),
+                group.leaders.to_string(),
+                group.total.to_string(),
+                group
+                    .leader_ratio
+                    .map(|ratio| format!("{:.2}", ratio))
+                    .unwrap_or_default(),
+                group.genders.female.to_string(),
+                group.genders.male.to_string(),
+                group.genders.unknown.to_string(),
+                group.without_birthday.to_string(),
+            ];
+            for stufe in STUFEN.iter() {
+                let counts = group
+                    .stufen
+                    .iter()
+                    .find(|counts| counts.stufe == stufe.class_name());
+                record.push(counts.map_or(0, |c| c.participants).to_string());
+                record.push(counts.map_or(0, |c| c.leaders).to_string());
+            }
+            for year in &years {
+                let counts = group.years.iter().find(|counts| counts.year == *year);
+                record.push(counts.map_or(0, |c| c.entries).to_string());
+                record.push(counts.map_or(0, |c| c.leavers).to_string());
+            }
+            csv.write_record(&record)?;
+        }
+        csv.flush()?;
+        Ok(())
+    }
+
+    /// Statistics as CSV, see write_csv()
+    pub fn to_csv(&self) -> Vec<u8> {
+        let mut csv: Vec<u8> = vec![];
+        self.write_csv(&mut csv)
+            .expect("writing to memory can not fail");
+        csv
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::midata::Group;
+
+    fn tree() -> GroupTree {
+        let groups: Vec<Group> = serde_json::from_str(
+            r#"[
+                {"id": "1", "group_type": "Group::Abteilung", "name": "Pfadi Muster"},
+                {"id": "2", "group_type": "Group::Woelfe", "name": "Meute Akela"},
+                {"id": "3", "group_type": "Group::Pfadi", "name": "Trupp Falke"}
+            ]"#,
+        )
+        .unwrap();
+        let mut tree = GroupTree::of(&groups[0]);
+        tree.children = groups[1..].iter().map(GroupTree::of).collect();
+        tree
+    }
+
+    fn people() -> Vec<Person> {
+        let role = |id: &str, role_type: &str, group: &str, created: &str, deleted: &str| {
+            format!(
+                r#"{{"id": "{}", "role_type": "{}", "created_at": "{}T10:00:00+02:00",
+                     "updated_at": "", "deleted_at": {},
+                     "links": {{"group": "{}", "layer_group": "1"}}}}"#,
+                id,
+                role_type,
+                created,
+                match deleted {
+                    "" => "null".to_string(),
+                    deleted => format!("\"{}T10:00:00+02:00\"", deleted),
+                },
+                group
+            )
+        };
+        serde_json::from_str(&format!(
+            r#"[
+                {{"id": "10", "first_name": "Anna", "company": false, "gender": "w",
+                  "birthday": "2015-06-01", "links": {{}}, "roles": [{}]}},
+                {{"id": "11", "first_name": "Ben", "company": false, "gender": "m",
+                  "birthday": "2012-01-01", "links": {{}}, "roles": [{}, {}]}},
+                {{"id": "12", "first_name": "Carla", "company": false, "gender": "w",
+                  "links": {{}}, "roles": [{}, {}]}},
+                {{"id": "13", "first_name": "Dario", "company": false,
+                  "birthday": "2015-02-01", "links": {{}}, "roles": [{}]}},
+                {{"id": "10", "first_name": "Anna", "company": false, "links": {{}},
+                  "roles": [{}]}}
+            ]"#,
+            role("100", "Group::Woelfe::Wolf", "2", "2023-08-20", ""),
+            role(
+                "101",
+                "Group::Woelfe::Wolf",
+                "2",
+                "2021-08-20",
+                "2023-08-19"
+            ),
+            role("102", "Group::Pfadi::Pfadi", "3", "2023-08-20", ""),
+            role(
+                "103",
+                "Group::Woelfe::Einheitsleitung",
+                "2",
+                "2020-01-10",
+                ""
+            ),
+            role(
+                "104",
+                "Group::Abteilung::Abteilungsleitung",
+                "1",
+                "2022-03-01",
+                ""
+            ),
+            role(
+                "105",
+                "Group::Woelfe::Wolf",
+                "2",
+                "2022-08-20",
+                "2023-06-30"
+            ),
+            role("100", "Group::Woelfe::Wolf", "2", "2023-08-20", "")
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn statistics() {
+        let on = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let stats = GroupStats::compute(&tree(), &people(), on);
+        assert_eq!((stats.participants, stats.leaders, stats.total), (2, 1, 3));
+        assert_eq!(stats.leader_ratio, Some(0.5));
+        assert_eq!(
+            stats.stufen,
+            vec![
+                StufeStats {
+                    stufe: "Group::Woelfe".to_string(),
+                    participants: 1,
+                    leaders: 1
+                },
+                StufeStats {
+                    stufe: "Group::Pfadi".to_string(),
+                    participants: 1,
+                    leaders: 0
+                }
+            ]
+        );
+        assert_eq!(
+            stats.genders,
+            GenderCounts {
+                female: 2,
+                male: 1,
+                unknown: 0
+            }
+        );
+        let ages: Vec<(u32, usize)> = stats
+            .ages
+            .iter()
+            .map(|a| (a.age, a.genders.female + a.genders.male))
+            .collect();
+        assert_eq!(ages, vec![(8, 1), (12, 1)]);
+        assert_eq!(stats.without_birthday, 1);
+        let years: Vec<(i32, usize, usize)> = stats
+            .years
+            .iter()
+            .map(|y| (y.year, y.entries, y.leavers))
+            .collect();
+        assert_eq!(
+            years,
+            vec![(2020, 1, 0), (2021, 1, 0), (2022, 1, 0), (2023, 1, 1)]
+        );
+
+        let pfadi = &stats.children[1];
+        assert_eq!((pfadi.participants, pfadi.leaders, pfadi.total), (1, 0, 1));
+        assert_eq!(
+            pfadi.years,
+            vec![YearStats {
+                year: 2023,
+                entries: 1,
+                leavers: 0
+            }]
+        );
+
+        let json = stats.to_json();
+        let parsed: GroupStats = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, stats);
+    }
+
+    #[test]
+    fn csv() {
+        let on = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let stats = GroupStats::compute(&tree(), &people(), on);
+        let csv = String::from_utf8(stats.to_csv()).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with(
+            "\u{feff}depth;group_id;group;group_type;participants;leaders;total;leader_ratio;"
+        ));
+        assert!(lines[0].ends_with(";entries_2023;leavers_2023"));
+        assert!(lines[1].starts_with("0;1;Pfadi Muster;Group::Abteilung;2;1;3;0.50;2;1;0;1;"));
+        assert!(lines[3].starts_with("1;3;Trupp Falke;Group::Pfadi;1;0;1;0.00;0;1;0;0;"));
+    }
+}
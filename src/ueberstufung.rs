@@ -0,0 +1,547 @@
+//! Planning and execution of the Überstufung, the yearly transition of participants of an
+//! Abteilung to the next stufe (e.g. from the Wölfe to the Pfadi).
+//!
+//! A plan is created from the ages of the participants, can be exported as CSV or JSON for
+//! review, edited (e.g. to choose a target group) and executed either as dry-run or for real.
+
+use crate::export::{csv_writer, CsvOptions, Delimiter};
+use crate::midata::{
+    ApiQuery, Error, Group, GroupType, MidataConnection, PeopleQuery, PeopleRange, Person,
+};
+use chrono::{Datelike, NaiveDate};
+use std::collections::HashMap;
+
+/// participant roles of the stufen as (label, class name), the first one is given to people
+/// moving up into the stufe
+fn participant_roles(group_type: &GroupType) -> &'static [(&'static str, &'static str)] {
+    match group_type {
+        GroupType::Biber => &[("Biber", "Group::Biber::Biber")],
+        GroupType::Woelfe => &[
+            ("Wolf", "Group::Woelfe::Wolf"),
+            ("Leitwolf", "Group::Woelfe::Leitwolf"),
+        ],
+        GroupType::Pfadi => &[
+            ("Pfadi", "Group::Pfadi::Pfadi"),
+            ("Leitpfadi", "Group::Pfadi::Leitpfadi"),
+        ],
+        GroupType::Pio => &[("Pio", "Group::Pio::Pio")],
+        GroupType::Rover => &[("Rover", "Group::AbteilungsRover::Rover")],
+        _ => &[],
+    }
+}
+
+/// the stufe following the given one
+fn next_stufe(group_type: &GroupType) -> Option<GroupType> {
+    match group_type {
+        GroupType::Biber => Some(GroupType::Woelfe),
+        GroupType::Woelfe => Some(GroupType::Pfadi),
+        GroupType::Pfadi => Some(GroupType::Pio),
+        GroupType::Pio => Some(GroupType::Rover),
+        _ => None,
+    }
+}
+
+/// age in completed years on the given date
+fn age_on(birthday: NaiveDate, date: NaiveDate) -> u32 {
+    let before_birthday = (date.month(), date.day()) < (birthday.month(), birthday.day());
+    (date.year() - birthday.year() - before_birthday as i32).max(0) as u32
+}
+
+/// Age at which the participants leave a stufe
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AgeCutoffs {
+    cutoffs: HashMap<GroupType, u32>,
+}
+
+impl Default for AgeCutoffs {
+    /// The ages recommended by the PBS: Biber until 7, Wölfe until 11, Pfadi until 14 and Pio
+    /// until 16.
+    fn default() -> Self {
+        AgeCutoffs::none()
+            .with(GroupType::Biber, 7)
+            .with(GroupType::Woelfe, 11)
+            .with(GroupType::Pfadi, 14)
+            .with(GroupType::Pio, 16)
+    }
+}
+
+impl AgeCutoffs {
+    /// no participant leaves any stufe
+    pub fn none() -> Self {
+        AgeCutoffs {
+            cutoffs: HashMap::new(),
+        }
+    }
+
+    /// set the age at which participants leave groups of the given type
+    pub fn with(mut self, group_type: GroupType, age: u32) -> Self {
+        self.cutoffs.insert(group_type, age);
+        self
+    }
+
+    /// age at which participants leave groups of the given type. None if they never do.
+    pub fn get(&self, group_type: &GroupType) -> Option<u32> {
+        self.cutoffs.get(group_type).copied()
+    }
+}
+
+/// Proposed transition of one person to the next stufe
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PlannedTransfer {
+    pub person_id: String,
+    pub name: String,
+    pub birthday: Option<NaiveDate>,
+    /// age on the day of the Überstufung
+    pub age: Option<u32>,
+    /// id of the role to end
+    pub role_id: String,
+    pub from_group_id: String,
+    pub from_group: String,
+    /// type of the role to end, e.g. `Group::Woelfe::Wolf`
+    pub from_role: String,
+    /// group of the new role. The transfer is skipped if not set.
+    pub to_group_id: Option<String>,
+    pub to_group: Option<String>,
+    /// type of the new role, e.g. `Group::Pfadi::Pfadi`
+    pub to_role: String,
+    /// reason why the transfer needs to be reviewed
+    pub note: Option<String>,
+}
+
+/// Plan of the Überstufung of an Abteilung
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct UeberstufungPlan {
+    pub abteilung_id: String,
+    /// first day in the new stufe
+    pub on: NaiveDate,
+    pub transfers: Vec<PlannedTransfer>,
+}
+
+/// How a plan is executed
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ExecutionMode {
+    /// only check the transfers could be executed
+    DryRun,
+    /// create the new roles and end the old ones
+    Apply,
+}
+
+/// Outcome of executing a planned transfer
+#[derive(Debug)]
+pub enum TransferOutcome {
+    /// the dry-run found no problems
+    Possible,
+    /// the person got the new role with the given id
+    Transferred(String),
+    /// the transfer was not executed, e.g. because no target group was chosen
+    Skipped(String),
+    Failed(Error),
+}
+
+/// Result of executing a planned transfer, see UeberstufungPlan::execute()
+#[derive(Debug)]
+pub struct TransferResult {
+    pub transfer: PlannedTransfer,
+    pub outcome: TransferOutcome,
+}
+
+impl std::fmt::Display for TransferResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let transfer = &self.transfer;
+        write!(
+            f,
+            "{} ({}): {} -> {}: ",
+            transfer.name,
+            transfer.person_id,
+            transfer.from_group,
+            transfer.to_group.as_deref().unwrap_or("?")
+        )?;
+        match &self.outcome {
+            TransferOutcome::Possible => write!(f, "ok (dry-run)"),
+            TransferOutcome::Transferred(role_id) => write!(f, "transferred (role {})", role_id),
+            TransferOutcome::Skipped(reason) => write!(f, "skipped, {}", reason),
+            TransferOutcome::Failed(e) => write!(f, "failed, {}", e),
+        }
+    }
+}
+
+/// Propose the transfers of the participants whose age on `on` reached the cutoff of their stufe.
+///
+/// # Arguments
+/// abteilung_id: id of the Abteilung the plan is for
+/// people: people of the Abteilung with their roles
+/// groups: groups of the Abteilung, used to find the groups of the roles and the target groups
+/// on: first day in the new stufe
+/// cutoffs: ages at which participants leave their stufe
+///
+/// # Note
+/// People without birthday are listed without target group so they can be reviewed. If the
+/// Abteilung has several groups of the next stufe, the target group has to be chosen as well.
+pub fn plan_transfers(
+    abteilung_id: &str,
+    people: &[Person],
+    groups: &[Group],
+    on: NaiveDate,
+    cutoffs: &AgeCutoffs,
+) -> UeberstufungPlan {
+    let mut transfers: Vec<PlannedTransfer> = vec![];
+    for person in people {
+        for role in &person.roles {
+            let group = match role
+                .links
+                .as_ref()
+                .and_then(|links| groups.iter().find(|group| group.id == links.group))
+            {
+                Some(group) => group,
+                None => continue,
+            };
+            let group_type = group.group_type();
+            let from_role =
+                match participant_roles(&group_type)
+                    .iter()
+                    .find(|(label, class_name)| {
+                        role.role_type == *label || role.role_type == *class_name
+                    }) {
+                    Some((_, class_name)) => class_name,
+                    None => continue,
+                };
+            let (next_type, cutoff) = match (next_stufe(&group_type), cutoffs.get(&group_type)) {
+                (Some(next_type), Some(cutoff)) => (next_type, cutoff),
+                _ => continue,
+            };
+            let age = person.birthday.map(|birthday| age_on(birthday, on));
+            if age.is_some_and(|age| age < cutoff) {
+                continue;
+            }
+
+            let targets: Vec<&Group> = groups
+                .iter()
+                .filter(|group| group.group_type() == next_type)
+                .collect();
+            let (target, note) = match (age, targets.as_slice()) {
+                (None, _) => (None, Some("birthday unknown".to_string())),
+                (_, [target]) => (Some(*target), None),
+                (_, []) => (
+                    None,
+                    Some(format!("no group of type {}", next_type.class_name())),
+                ),
+                (_, targets) => {
+                    let names: Vec<&str> =
+                        targets.iter().map(|group| group.name.as_str()).collect();
+                    (None, Some(format!("choose one of {}", names.join(", "))))
+                }
+            };
+
+            transfers.push(PlannedTransfer {
+                person_id: person.id.clone(),
+                name: person.display_name(),
+                birthday: person.birthday,
+                age,
+                role_id: role.id.clone(),
+                from_group_id: group.id.clone(),
+                from_group: group.name.clone(),
+                from_role: from_role.to_string(),
+                to_group_id: target.map(|group| group.id.clone()),
+                to_group: target.map(|group| group.name.clone()),
+                to_role: participant_roles(&next_type)[0].1.to_string(),
+                note,
+            });
+        }
+    }
+    transfers.sort_by(|a, b| {
+        (&a.from_group, &a.name, &a.person_id).cmp(&(&b.from_group, &b.name, &b.person_id))
+    });
+    UeberstufungPlan {
+        abteilung_id: abteilung_id.to_string(),
+        on,
+        transfers,
+    }
+}
+
+impl UeberstufungPlan {
+    /// the plan as JSON, see from_json()
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("a plan can always be serialized")
+    }
+
+    /// read a plan written by to_json(), e.g. after choosing the target groups
+    pub fn from_json(json: &str) -> Result<UeberstufungPlan, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    /// the transfers as CSV with one line per transfer (comma separated, with header)
+    pub fn to_csv(&self) -> String {
+        let options = CsvOptions {
+            delimiter: Delimiter::Comma,
+            bom: false,
+            ..CsvOptions::default()
+        };
+        let mut csv = csv_writer(vec![], &options).expect("writing to memory can not fail");
+        csv.write_record([
+            "person_id",
+            "name",
+            "birthday",
+            "age",
+            "role_id",
+            "from_group_id",
+            "from_group",
+            "from_role",
+            "to_group_id",
+            "to_group",
+            "to_role",
+            "note",
+        ])
+        .expect("writing to memory can not fail");
+        for transfer in &self.transfers {
+            let fields = [
+                transfer.person_id.clone(),
+                transfer.name.clone(),
+                transfer
+                    .birthday
+                    .map(|birthday| birthday.to_string())
+                    .unwrap_or_default(),
+                transfer.age.map(|age| age.to_string()).unwrap_or_default(),
+                transfer.role_id.clone(),
+                transfer.from_group_id.clone(),
+                transfer.from_group.clone(),
+                transfer.from_role.clone(),
+                transfer.to_group_id.clone().unwrap_or_default(),
+                transfer.to_group.clone().unwrap_or_default(),
+                transfer.to_role.clone(),
+                transfer.note.clone().unwrap_or_default(),
+            ];
+            csv.write_record(&fields)
+                .expect("writing to memory can not fail");
+        }
+        let csv = csv.into_inner().expect("writing to memory can not fail");
+        String::from_utf8(csv).expect("the fields are UTF-8")
+    }
+
+    /// Execute the planned transfers.
+    ///
+    /// # Arguments
+    /// connection: connection used to load the people and groups (using the JSON:API) and to
+    /// modify the roles
+    /// mode: DryRun only checks that the person still has the role and that the new role type
+    /// exists in the target group, Apply transfers the roles with transfer_role().
+    ///
+    /// # Note
+    /// A failed transfer does not stop the execution, the result of every transfer is returned.
+    pub fn execute(
+        &self,
+        connection: &MidataConnection,
+        mode: ExecutionMode,
+    ) -> Vec<TransferResult> {
+        self.transfers
+            .iter()
+            .map(|transfer| TransferResult {
+                transfer: transfer.clone(),
+                outcome: self.execute_transfer(connection, transfer, mode),
+            })
+            .collect()
+    }
+
+    fn execute_transfer(
+        &self,
+        connection: &MidataConnection,
+        transfer: &PlannedTransfer,
+        mode: ExecutionMode,
+    ) -> TransferOutcome {
+        let to_group_id = match &transfer.to_group_id {
+            Some(id) => id,
+            None => return TransferOutcome::Skipped("no target group".to_string()),
+        };
+
+        let query = ApiQuery::new().filter("id", to_group_id);
+        let group = match connection
+            .query_groups(&query)
+            .map(|mut groups| groups.pop())
+        {
+            Ok(Some(group)) => group,
+            Ok(None) => return TransferOutcome::Skipped("target group not found".to_string()),
+            Err(e) => return TransferOutcome::Failed(e),
+        };
+        let group_type = group.group_type();
+        if !group_type.allows_role_type(&transfer.to_role) {
+            return TransferOutcome::Failed(Error::InvalidRoleType {
+                role_type: transfer.to_role.clone(),
+                group_type: group_type.class_name().to_string(),
+            });
+        }
+        let query = ApiQuery::new()
+            .filter("id", &transfer.person_id)
+            .include("roles");
+        let person = match connection
+            .query_people(&query)
+            .map(|mut people| people.pop())
+        {
+            Ok(Some(person)) => person,
+            Ok(None) => return TransferOutcome::Skipped("person not found".to_string()),
+            Err(e) => return TransferOutcome::Failed(e),
+        };
+        let role = match person.roles.iter().find(|role| role.id == transfer.role_id) {
+            Some(role) => role,
+            None => return TransferOutcome::Skipped("role no longer exists".to_string()),
+        };
+
+        match mode {
+            ExecutionMode::DryRun => TransferOutcome::Possible,
+            ExecutionMode::Apply => {
+                match connection.transfer_role(&person, role, &group, &transfer.to_role, self.on) {
+                    Ok(role) => TransferOutcome::Transferred(role.id),
+                    Err(e) => TransferOutcome::Failed(e),
+                }
+            }
+        }
+    }
+}
+
+/// Plan the Überstufung of an Abteilung. See plan_transfers().
+///
+/// # Arguments
+/// abteilung_id: id of the Abteilung
+/// on: first day in the new stufe
+/// cutoffs: ages at which participants leave their stufe
+pub fn plan_ueberstufung(
+    connection: &MidataConnection,
+    abteilung_id: u16,
+    on: NaiveDate,
+    cutoffs: &AgeCutoffs,
+) -> UeberstufungPlan {
+    let abteilung = connection.load_group(abteilung_id);
+    let query = PeopleQuery {
+        range: PeopleRange::Layer,
+        ..PeopleQuery::default()
+    };
+    let people = connection.load_people_of_group_with(abteilung_id, query);
+    // the birthday is not always part of the list of people
+    let (mut people, without_birthday): (Vec<Person>, Vec<Person>) = people
+        .into_iter()
+        .partition(|person| person.birthday.is_some());
+    if !without_birthday.is_empty() {
+        people.append(&mut connection.load_details_of_people(without_birthday));
+    }
+
+    let mut group_ids: Vec<u16> = people
+        .iter()
+        .flat_map(|person| &person.roles)
+        .filter_map(|role| role.links.as_ref()?.group.parse().ok())
+        .chain(
+            abteilung
+                .chilrden
+                .iter()
+                .filter_map(|group| group.id.parse().ok()),
+        )
+        .collect();
+    group_ids.sort_unstable();
+    group_ids.dedup();
+    let groups = connection.load_groups(group_ids);
+
+    plan_transfers(&abteilung.id, &people, &groups, on, cutoffs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn groups() -> Vec<Group> {
+        serde_json::from_str(
+            r#"[
+                {"id": "1", "group_type": "Abteilung", "name": "Pfadi Muster"},
+                {"id": "2", "group_type": "Wölfe", "name": "Meute Akela"},
+                {"id": "3", "group_type": "Pfadi", "name": "Trupp Falke"},
+                {"id": "4", "group_type": "Pio", "name": "Pios Nord"},
+                {"id": "5", "group_type": "Pio", "name": "Pios Süd"}
+            ]"#,
+        )
+        .unwrap()
+    }
+
+    fn people() -> Vec<Person> {
+        serde_json::from_str(
+            r#"[
+                {"id": "10", "first_name": "Anna", "last_name": "Muster", "company": false,
+                 "birthday": "2013-05-01", "links": {},
+                 "roles": [{"id": "100", "role_type": "Wolf", "created_at": "",
+                            "updated_at": "", "links": {"group": "2", "layer_group": "1"}}]},
+                {"id": "11", "first_name": "Ben", "last_name": "Muster", "company": false,
+                 "birthday": "2013-09-01", "links": {},
+                 "roles": [{"id": "101", "role_type": "Leitwolf", "created_at": "",
+                            "updated_at": "", "links": {"group": "2", "layer_group": "1"}}]},
+                {"id": "12", "first_name": "Clara", "last_name": "Beispiel", "company": false,
+                 "birthday": "2010-01-01", "links": {},
+                 "roles": [{"id": "102", "role_type": "Pfadi", "created_at": "",
+                            "updated_at": "", "links": {"group": "3", "layer_group": "1"}}]},
+                {"id": "13", "first_name": "Dario", "company": false, "links": {},
+                 "roles": [{"id": "103", "role_type": "Wolf", "created_at": "",
+                            "updated_at": "", "links": {"group": "2", "layer_group": "1"}}]},
+                {"id": "14", "first_name": "Eva", "company": false, "birthday": "1990-01-01",
+                 "links": {},
+                 "roles": [{"id": "104", "role_type": "Einheitsleitung", "created_at": "",
+                            "updated_at": "", "links": {"group": "2", "layer_group": "1"}}]}
+            ]"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn age() {
+        let birthday = NaiveDate::from_ymd_opt(2013, 8, 20).unwrap();
+        assert_eq!(
+            age_on(birthday, NaiveDate::from_ymd_opt(2024, 8, 19).unwrap()),
+            10
+        );
+        assert_eq!(
+            age_on(birthday, NaiveDate::from_ymd_opt(2024, 8, 20).unwrap()),
+            11
+        );
+    }
+
+    #[test]
+    fn plan() {
+        let on = NaiveDate::from_ymd_opt(2024, 8, 20).unwrap();
+        let plan = plan_transfers("1", &people(), &groups(), on, &AgeCutoffs::default());
+
+        let ids: Vec<&str> = plan
+            .transfers
+            .iter()
+            .map(|t| t.person_id.as_str())
+            .collect();
+        assert_eq!(ids, vec!["10", "13", "12"]);
+
+        let anna = &plan.transfers[0];
+        assert_eq!(anna.age, Some(11));
+        assert_eq!(anna.from_role, "Group::Woelfe::Wolf");
+        assert_eq!(anna.to_group_id.as_deref(), Some("3"));
+        assert_eq!(anna.to_role, "Group::Pfadi::Pfadi");
+
+        let dario = &plan.transfers[1];
+        assert_eq!(dario.to_group_id, None);
+        assert_eq!(dario.note.as_deref(), Some("birthday unknown"));
+
+        let clara = &plan.transfers[2];
+        assert_eq!(clara.to_group_id, None);
+        assert_eq!(
+            clara.note.as_deref(),
+            Some("choose one of Pios Nord, Pios Süd")
+        );
+    }
+
+    #[test]
+    fn plan_as_csv_and_json() {
+        let on = NaiveDate::from_ymd_opt(2024, 8, 20).unwrap();
+        let plan = plan_transfers("1", &people(), &groups(), on, &AgeCutoffs::default());
+
+        let csv = plan.to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(
+            lines[1],
+            "10,Anna Muster,2013-05-01,11,100,2,Meute Akela,Group::Woelfe::Wolf,3,Trupp Falke,\
+             Group::Pfadi::Pfadi,"
+        );
+        assert!(lines[3].ends_with(",\"choose one of Pios Nord, Pios Süd\""));
+
+        assert_eq!(UeberstufungPlan::from_json(&plan.to_json()).unwrap(), plan);
+    }
+}